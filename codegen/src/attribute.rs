@@ -285,7 +285,7 @@ impl Parse for AttributeArgument {
                     let str_lit = to_string_literal(arg.lit)?;
 
                     let _ = regex::Regex::new(str_lit.value().as_str()).map_err(|e| {
-                        syn::Error::new(str_lit.span(), format!("Invalid regex: {}", e.to_string()))
+                        syn::Error::new(str_lit.span(), format!("Invalid regex: {}", e))
                     })?;
 
                     Ok(AttributeArgument::Regex(str_lit))
@@ -355,13 +355,11 @@ fn remove_attr(
 
 /// Compares attribute's path and argument.
 fn eq_path_and_arg((attr_path, attr_arg): (&str, &str), attr: &syn::Attribute) -> bool {
-    if let Ok(meta) = attr.parse_meta() {
-        if let syn::Meta::List(meta_list) = meta {
-            if meta_list.path.is_ident(attr_path) && meta_list.nested.len() == 1 {
-                // Unwrapping is OK here, because `meta_list.nested.len() == 1`.
-                if let syn::NestedMeta::Meta(m) = meta_list.nested.first().unwrap() {
-                    return m.path().is_ident(attr_arg);
-                }
+    if let Ok(syn::Meta::List(meta_list)) = attr.parse_meta() {
+        if meta_list.path.is_ident(attr_path) && meta_list.nested.len() == 1 {
+            // Unwrapping is OK here, because `meta_list.nested.len() == 1`.
+            if let syn::NestedMeta::Meta(m) = meta_list.nested.first().unwrap() {
+                return m.path().is_ident(attr_arg);
             }
         }
     }
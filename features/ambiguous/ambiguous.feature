@@ -0,0 +1,4 @@
+Feature: Steps matched by several step functions
+  Scenario: An ambiguous step
+    When a step matching two patterns
+    Then it's okay
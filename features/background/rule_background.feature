@@ -0,0 +1,10 @@
+Feature: Backgrounds of rules
+  Background:
+    Given the feature is set up
+
+  Rule: A rule with its own background
+    Background:
+      Given the rule is set up
+
+    Scenario: A scenario in the rule
+      Then everything is set up
@@ -0,0 +1,16 @@
+Feature: Scenarios that wait on something slow
+  Scenario: The first slow scenario
+    When something slow happens
+    Then it's okay
+
+  Scenario: The second slow scenario
+    When something slow happens
+    Then it's okay
+
+  Scenario: The third slow scenario
+    When something slow happens
+    Then it's okay
+
+  Scenario: The fourth slow scenario
+    When something slow happens
+    Then it's okay
@@ -0,0 +1,15 @@
+Feature: Outlines with several Examples blocks
+  Scenario Outline: An outline with named examples
+    When something
+    Then it's <outcome>
+
+    @fast
+    Examples: valid inputs
+      | outcome |
+      | okay    |
+      | okay    |
+
+    @slow
+    Examples: edge cases
+      | outcome |
+      | okay    |
@@ -0,0 +1,22 @@
+@db
+Feature: Hooks selected by criteria
+  Scenario: A scenario writing to the database
+    When something
+    Then it's okay
+
+  @readonly
+  Scenario: A scenario reading from the database
+    When something
+    Then it's okay
+
+  @audited
+  Rule: An audited rule
+
+    Scenario: A scenario writing inside the rule
+      When something
+      Then it's okay
+
+    @readonly
+    Scenario: A scenario reading inside the rule
+      When something
+      Then it's okay
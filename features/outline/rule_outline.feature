@@ -0,0 +1,10 @@
+Feature: Outlines inside rules
+  Rule: A rule with an outline
+    Scenario Outline: A number is checked
+      When the number <n> is picked
+      Then it's okay
+
+      Examples:
+        | n |
+        | 1 |
+        | 2 |
@@ -0,0 +1,13 @@
+Feature: Placeholders in step arguments
+  Scenario Outline: Sending <name>
+    When the request is sent
+      """
+      {"name": "<name>"}
+      """
+    Then the response contains
+      | field | value  |
+      | name  | <name> |
+
+    Examples:
+      | name  |
+      | alice |
@@ -0,0 +1,19 @@
+Feature: Rerunning failed scenarios
+  Scenario: A passing scenario
+    When something
+    Then it's okay
+
+  Scenario: A failing scenario
+    When something
+    Then it's not okay
+
+  Scenario Outline: An outline with a failing example
+    When something
+    Then it's <outcome>
+
+    Examples:
+      | outcome  |
+      | okay     |
+
+      # The row expected to fail
+      | not okay |
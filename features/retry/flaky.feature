@@ -0,0 +1,5 @@
+Feature: Scenarios that need another go
+  @retry-2
+  Scenario: A scenario which passes on its second attempt
+    When something
+    Then it's flaky
@@ -0,0 +1,5 @@
+Feature: Scenarios running alongside serial ones
+  Scenario: A long scenario alongside
+    When something slow happens
+    And something slow happens
+    Then it's okay
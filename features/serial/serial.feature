@@ -0,0 +1,13 @@
+Feature: Scenarios that must not overlap
+  Scenario: A slow scenario before
+    When something slow happens
+    Then it's okay
+
+  @serial
+  Scenario: A slow scenario on its own
+    When something slow happens
+    Then it's okay
+
+  Scenario: A slow scenario after
+    When something slow happens
+    Then it's okay
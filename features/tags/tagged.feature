@@ -0,0 +1,36 @@
+@smoke
+Feature: Scenarios selected by tag expressions
+  Scenario: A plain smoke test
+    When something
+    Then it's okay
+
+  @wip
+  Scenario: A scenario still in progress
+    When something
+    Then it's okay
+
+  @slow
+  Scenario: A slow scenario
+    When something
+    Then it's okay
+
+  Scenario Outline: An outline with slow examples
+    When something
+    Then it's <outcome>
+
+    @slow
+    Examples:
+      | outcome |
+      | okay    |
+
+  @fast
+  Rule: A tagged rule
+
+    Scenario: A scenario inheriting its tags
+      When something
+      Then it's okay
+
+    @wip
+    Scenario: A scenario in progress inside a rule
+      When something
+      Then it's okay
@@ -1,10 +1,22 @@
-use clap::{App, Arg};
+use clap::{value_t, App, Arg};
+use gherkin::tagexpr::TagOperation;
+
+use crate::{Order, Shard};
 
 #[derive(Default)]
 pub struct CliOptions {
     pub scenario_filter: Option<String>,
+    pub tag_filter: Option<TagOperation>,
     pub nocapture: bool,
     pub debug: bool,
+    pub concurrency: Option<usize>,
+    pub fail_fast: bool,
+    pub retries: Option<u32>,
+    pub dry_run: bool,
+    pub order: Option<Order>,
+    pub shard: Option<Shard>,
+    pub rerun: Option<String>,
+    pub rerun_file: Option<String>,
 }
 
 pub fn make_app() -> CliOptions {
@@ -20,6 +32,14 @@ pub fn make_app() -> CliOptions {
                 .help("Regex to select scenarios from")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("tags")
+                .short("t")
+                .long("tags")
+                .value_name("expression")
+                .help("Tag expression to select scenarios from, e.g. \"@smoke and not @wip\"")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("nocapture")
                 .long("nocapture")
@@ -30,15 +50,108 @@ pub fn make_app() -> CliOptions {
                 .long("debug")
                 .help("Enable verbose test logging (debug mode)"),
         )
+        .arg(
+            Arg::with_name("concurrency")
+                .long("concurrency")
+                .value_name("n")
+                .help(
+                    "Maximum number of scenarios to run at the same time. Above 1, features \
+                     and their hooks run concurrently too, and output is not captured",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fail-fast")
+                .long("fail-fast")
+                .help("Stop running new scenarios after the first failure"),
+        )
+        .arg(
+            Arg::with_name("retry")
+                .long("retry")
+                .value_name("n")
+                .help("Number of times to retry a failing scenario")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .value_name("order")
+                .help("Order to run scenarios in: defined, random or random:SEED")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("shard")
+                .long("shard")
+                .value_name("INDEX/TOTAL")
+                .help("Only run the scenarios of one of TOTAL shards, numbered from 1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rerun")
+                .long("rerun")
+                .value_name("file")
+                .help("Only run the scenarios listed in a rerun file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rerun-file")
+                .long("rerun-file")
+                .value_name("file")
+                .help("Write the location of every failed scenario to a rerun file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Check that every step is defined without running anything"),
+        )
         .get_matches();
 
     let nocapture = matches.is_present("nocapture");
     let scenario_filter = matches.value_of("filter").map(|v| v.to_string());
     let debug = matches.is_present("debug");
+    let fail_fast = matches.is_present("fail-fast");
+    let dry_run = matches.is_present("dry-run");
+    let rerun = matches.value_of("rerun").map(|v| v.to_string());
+    let rerun_file = matches.value_of("rerun-file").map(|v| v.to_string());
+    let concurrency = if matches.is_present("concurrency") {
+        Some(value_t!(matches, "concurrency", usize).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+    let tag_filter = if matches.is_present("tags") {
+        Some(value_t!(matches, "tags", TagOperation).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+    let order = if matches.is_present("order") {
+        Some(value_t!(matches, "order", Order).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+    let shard = if matches.is_present("shard") {
+        Some(value_t!(matches, "shard", Shard).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+    let retries = if matches.is_present("retry") {
+        Some(value_t!(matches, "retry", u32).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
 
     CliOptions {
         nocapture,
         scenario_filter,
+        tag_filter,
         debug,
+        concurrency,
+        fail_fast,
+        retries,
+        dry_run,
+        order,
+        shard,
+        rerun,
+        rerun_file,
     }
 }
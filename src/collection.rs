@@ -16,6 +16,14 @@ use crate::runner::{StepFn, TestFunction};
 use crate::World;
 use gherkin::{Step, StepType};
 
+/// The step function a step resolves to, if there is exactly one.
+pub(crate) enum Resolution<W> {
+    Found(TestFunction<W>),
+    /// More than one regex matches the step. Carries their patterns.
+    Ambiguous(Vec<String>),
+    Undefined,
+}
+
 #[derive(CustomDefault)]
 struct StepMaps<W: World> {
     #[def_exp = "BTreeMap::new()"]
@@ -59,7 +67,9 @@ impl<W: World> StepsCollection<W> {
         };
     }
 
-    pub(crate) fn resolve(&self, step: &Step) -> Option<TestFunction<W>> {
+    /// Looks up the step function of `step`. A literal step takes precedence
+    /// over regexes, and a step matched by several regexes is ambiguous.
+    pub(crate) fn resolve(&self, step: &Step) -> Resolution<W> {
         // Attempt to find literal variant of steps first
         let test_fn = match step.ty {
             StepType::Given => self.given.basic.get(&*step.value),
@@ -68,7 +78,7 @@ impl<W: World> StepsCollection<W> {
         };
 
         if let Some(function) = test_fn {
-            return Some(TestFunction::from(function));
+            return Resolution::Found(TestFunction::from(function));
         }
 
         #[allow(clippy::mutable_key_type)]
@@ -79,10 +89,20 @@ impl<W: World> StepsCollection<W> {
         };
 
         // Then attempt to find a regex variant of that test
-        if let Some((regex, function)) = regex_map
+        let mut found = regex_map
             .iter()
-            .find(|(regex, _)| regex.is_match(&step.value))
-        {
+            .filter(|(regex, _)| regex.is_match(&step.value))
+            .collect::<Vec<_>>();
+
+        if found.len() > 1 {
+            let patterns = found
+                .iter()
+                .map(|(regex, _)| regex.as_str().to_string())
+                .collect();
+            return Resolution::Ambiguous(patterns);
+        }
+
+        if let Some((regex, function)) = found.pop() {
             let matches = regex
                 .0
                 .captures(&step.value)
@@ -95,12 +115,12 @@ impl<W: World> StepsCollection<W> {
                 })
                 .collect();
 
-            return Some(match *function {
+            return Resolution::Found(match *function {
                 StepFn::Sync(x) => TestFunction::RegexSync(x, matches),
                 StepFn::Async(x) => TestFunction::RegexAsync(x, matches),
             });
         }
 
-        None
+        Resolution::Undefined
     }
 }
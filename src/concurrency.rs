@@ -0,0 +1,347 @@
+// Copyright (c) 2018-2021  Brendan Molloy <brendan@bbqsrc.net>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Single-threaded building blocks for running scenarios concurrently.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use futures::{Future, Stream};
+
+/// A counting semaphore for futures polled on a single thread.
+///
+/// Waiters are served strictly in the order they first asked, so a request
+/// for many permits cannot be starved by a stream of smaller ones.
+pub(crate) struct Semaphore {
+    state: RefCell<SemaphoreState>,
+}
+
+struct SemaphoreState {
+    capacity: usize,
+    available: usize,
+    next_ticket: u64,
+    waiters: VecDeque<(u64, Waker)>,
+}
+
+impl Semaphore {
+    pub fn new(capacity: usize) -> Rc<Semaphore> {
+        let capacity = capacity.max(1);
+        Rc::new(Semaphore {
+            state: RefCell::new(SemaphoreState {
+                capacity,
+                available: capacity,
+                next_ticket: 0,
+                waiters: VecDeque::new(),
+            }),
+        })
+    }
+
+    /// Acquire `count` permits, clamped to the capacity of the semaphore.
+    pub fn acquire(self: &Rc<Self>, count: usize) -> Acquire {
+        let count = count.min(self.state.borrow().capacity).max(1);
+        Acquire {
+            semaphore: Rc::clone(self),
+            count,
+            ticket: None,
+        }
+    }
+
+    /// Acquire every permit, running the holder exclusively.
+    pub fn acquire_all(self: &Rc<Self>) -> Acquire {
+        let capacity = self.state.borrow().capacity;
+        self.acquire(capacity)
+    }
+
+    fn wake_front(state: &SemaphoreState) {
+        if let Some((_, waker)) = state.waiters.front() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+pub(crate) struct Acquire {
+    semaphore: Rc<Semaphore>,
+    count: usize,
+    ticket: Option<u64>,
+}
+
+impl Future for Acquire {
+    type Output = Permit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit> {
+        let semaphore = Rc::clone(&self.semaphore);
+        let mut state = semaphore.state.borrow_mut();
+
+        let ticket = match self.ticket {
+            Some(ticket) => ticket,
+            None => {
+                let ticket = state.next_ticket;
+                state.next_ticket += 1;
+                state.waiters.push_back((ticket, cx.waker().clone()));
+                self.ticket = Some(ticket);
+                ticket
+            }
+        };
+
+        let is_front = state.waiters.front().map(|(t, _)| *t) == Some(ticket);
+        if is_front && state.available >= self.count {
+            state.available -= self.count;
+            state.waiters.pop_front();
+            self.ticket = None;
+            // The next waiter may fit into what is left over.
+            Semaphore::wake_front(&state);
+            return Poll::Ready(Permit {
+                semaphore: Rc::clone(&self.semaphore),
+                count: self.count,
+            });
+        }
+
+        if let Some(entry) = state.waiters.iter_mut().find(|(t, _)| *t == ticket) {
+            entry.1 = cx.waker().clone();
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            let mut state = self.semaphore.state.borrow_mut();
+            state.waiters.retain(|(t, _)| *t != ticket);
+            Semaphore::wake_front(&state);
+        }
+    }
+}
+
+/// Permits held from a `Semaphore`, returned when dropped.
+pub(crate) struct Permit {
+    semaphore: Rc<Semaphore>,
+    count: usize,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.semaphore.state.borrow_mut();
+        state.available += self.count;
+        Semaphore::wake_front(&state);
+    }
+}
+
+/// Polls up to `limit` streams from `streams` at the same time, yielding
+/// their items as if the streams had been run one after another.
+///
+/// Items of the first unfinished stream are passed through as soon as they
+/// are ready; items of the streams after it are buffered until it is done.
+pub(crate) fn ordered_merge<S, I>(streams: S, limit: usize) -> OrderedMerge<S, I>
+where
+    S: Stream<Item = I> + Unpin,
+    I: Stream + Unpin,
+{
+    OrderedMerge {
+        streams: Some(streams),
+        active: VecDeque::new(),
+        limit: limit.max(1),
+    }
+}
+
+struct Active<I: Stream> {
+    stream: I,
+    buffer: VecDeque<I::Item>,
+    done: bool,
+}
+
+pub(crate) struct OrderedMerge<S, I: Stream> {
+    streams: Option<S>,
+    active: VecDeque<Active<I>>,
+    limit: usize,
+}
+
+// Buffered items are never pinned, so moving them around is fine.
+impl<S: Unpin, I: Stream + Unpin> Unpin for OrderedMerge<S, I> {}
+
+impl<S, I> Stream for OrderedMerge<S, I>
+where
+    S: Stream<Item = I> + Unpin,
+    I: Stream + Unpin,
+{
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        let this = &mut *self;
+
+        loop {
+            while this.active.len() < this.limit {
+                let streams = match this.streams.as_mut() {
+                    Some(v) => v,
+                    None => break,
+                };
+                match Pin::new(streams).poll_next(cx) {
+                    Poll::Ready(Some(stream)) => this.active.push_back(Active {
+                        stream,
+                        buffer: VecDeque::new(),
+                        done: false,
+                    }),
+                    Poll::Ready(None) => this.streams = None,
+                    Poll::Pending => break,
+                }
+            }
+
+            let head = match this.active.front_mut() {
+                Some(v) => v,
+                None if this.streams.is_none() => return Poll::Ready(None),
+                None => return Poll::Pending,
+            };
+
+            if let Some(item) = head.buffer.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+
+            if !head.done {
+                match Pin::new(&mut head.stream).poll_next(cx) {
+                    Poll::Ready(Some(item)) => return Poll::Ready(Some(item)),
+                    Poll::Ready(None) => head.done = true,
+                    Poll::Pending => {}
+                }
+            }
+
+            if head.done {
+                this.active.pop_front();
+                continue;
+            }
+
+            // The head is waiting, so make progress on everything behind it.
+            for entry in this.active.iter_mut().skip(1) {
+                while !entry.done {
+                    match Pin::new(&mut entry.stream).poll_next(cx) {
+                        Poll::Ready(Some(item)) => entry.buffer.push_back(item),
+                        Poll::Ready(None) => entry.done = true,
+                        Poll::Pending => break,
+                    }
+                }
+            }
+
+            return Poll::Pending;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use futures::stream::{self, StreamExt};
+    use futures::task::noop_waker_ref;
+    use futures::FutureExt;
+
+    use super::*;
+
+    /// Yields its items only once the gate has been opened.
+    struct Gated {
+        open: Rc<Cell<bool>>,
+        items: VecDeque<u32>,
+    }
+
+    impl Stream for Gated {
+        type Item = u32;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<u32>> {
+            if self.open.get() {
+                Poll::Ready(self.items.pop_front())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    fn gated(open: &Rc<Cell<bool>>, items: &[u32]) -> Gated {
+        Gated {
+            open: Rc::clone(open),
+            items: items.iter().copied().collect(),
+        }
+    }
+
+    fn poll<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        future.poll_unpin(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    fn poll_stream<S: Stream + Unpin>(stream: &mut S) -> Poll<Option<S::Item>> {
+        stream.poll_next_unpin(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    #[test]
+    fn acquire_all_is_served_before_later_smaller_requests() {
+        let semaphore = Semaphore::new(3);
+        let held = match poll(&mut semaphore.acquire(1)) {
+            Poll::Ready(permit) => permit,
+            Poll::Pending => panic!("a free semaphore must grant a permit"),
+        };
+
+        let mut all = semaphore.acquire_all();
+        assert!(poll(&mut all).is_pending());
+
+        // Two permits are free, but the exclusive request asked first.
+        let mut one = semaphore.acquire(1);
+        assert!(poll(&mut one).is_pending());
+
+        drop(held);
+        let all_permit = match poll(&mut all) {
+            Poll::Ready(permit) => permit,
+            Poll::Pending => panic!("acquire_all must be served once permits return"),
+        };
+        assert!(poll(&mut one).is_pending());
+
+        drop(all_permit);
+        assert!(poll(&mut one).is_ready());
+    }
+
+    #[test]
+    fn dropping_a_pending_acquire_lets_the_next_waiter_through() {
+        let semaphore = Semaphore::new(2);
+        let held = match poll(&mut semaphore.acquire(1)) {
+            Poll::Ready(permit) => permit,
+            Poll::Pending => panic!("a free semaphore must grant a permit"),
+        };
+
+        let mut all = semaphore.acquire_all();
+        assert!(poll(&mut all).is_pending());
+        let mut one = semaphore.acquire(1);
+        assert!(poll(&mut one).is_pending());
+
+        drop(all);
+        assert!(poll(&mut one).is_ready());
+        drop(held);
+        assert!(semaphore.state.borrow().waiters.is_empty());
+    }
+
+    #[test]
+    fn buffered_items_come_out_in_stream_order() {
+        let open = Rc::new(Cell::new(false));
+        let streams = stream::iter(vec![
+            gated(&open, &[1, 2]),
+            gated(&Rc::new(Cell::new(true)), &[3, 4]),
+            gated(&Rc::new(Cell::new(true)), &[5]),
+        ]);
+        let mut merged = ordered_merge(streams, 3);
+
+        // The head is blocked, so the streams behind it are drained into
+        // their buffers without anything being yielded.
+        assert!(poll_stream(&mut merged).is_pending());
+        assert!(merged.active.iter().skip(1).all(|entry| entry.done));
+
+        open.set(true);
+        let mut items = vec![];
+        while let Poll::Ready(Some(item)) = poll_stream(&mut merged) {
+            items.push(item);
+        }
+        assert_eq!(items, [1, 2, 3, 4, 5]);
+        assert!(matches!(poll_stream(&mut merged), Poll::Ready(None)));
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{BitAnd, BitOr};
+use std::ops::{BitAnd, BitOr, Not};
 
 use gherkin::{Feature, Rule, Scenario};
 
@@ -39,13 +39,18 @@ impl Pattern {
 pub enum Criteria {
     And(Box<Criteria>, Box<Criteria>),
     Or(Box<Criteria>, Box<Criteria>),
+    Not(Box<Criteria>),
     Scenario(Pattern),
     Rule(Pattern),
     Feature(Pattern),
+    /// Matches a tag (without its leading `@`) set at the given level or
+    /// inherited from a level above it.
+    Tag(Context, String),
 }
 
+/// The level of the lifecycle a `Criteria` applies to.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub(crate) enum Context {
+pub enum Context {
     Scenario,
     Rule,
     Feature,
@@ -53,24 +58,15 @@ pub(crate) enum Context {
 
 impl Context {
     pub fn is_scenario(&self) -> bool {
-        match self {
-            Context::Scenario => true,
-            _ => false,
-        }
+        matches!(self, Context::Scenario)
     }
 
     pub fn is_rule(&self) -> bool {
-        match self {
-            Context::Rule => true,
-            _ => false,
-        }
+        matches!(self, Context::Rule)
     }
 
     pub fn is_feature(&self) -> bool {
-        match self {
-            Context::Feature => true,
-            _ => false,
-        }
+        matches!(self, Context::Feature)
     }
 }
 
@@ -79,9 +75,11 @@ impl Criteria {
         match self {
             Criteria::And(a, b) => std::cmp::min(a.context(), b.context()),
             Criteria::Or(a, b) => std::cmp::min(a.context(), b.context()),
+            Criteria::Not(a) => a.context(),
             Criteria::Scenario(_) => Context::Scenario,
             Criteria::Rule(_) => Context::Rule,
             Criteria::Feature(_) => Context::Feature,
+            Criteria::Tag(context, _) => *context,
         }
     }
 
@@ -98,11 +96,19 @@ impl Criteria {
             Criteria::Or(a, b) => {
                 a.eval(feature, rule, scenario) || b.eval(feature, rule, scenario)
             }
+            Criteria::Not(a) => !a.eval(feature, rule, scenario),
             Criteria::Scenario(pattern) if scenario.is_some() => {
                 pattern.eval(&scenario.unwrap().name)
             }
             Criteria::Rule(pattern) if rule.is_some() => pattern.eval(&rule.unwrap().name),
             Criteria::Feature(pattern) => pattern.eval(&feature.name),
+            Criteria::Tag(Context::Scenario, tag) if scenario.is_some() => {
+                crate::tags::inherited(feature, rule, scenario).any(|t| t == tag)
+            }
+            Criteria::Tag(Context::Rule, tag) if rule.is_some() => {
+                crate::tags::inherited(feature, rule, None).any(|t| t == tag)
+            }
+            Criteria::Tag(Context::Feature, tag) => feature.tags.contains(tag),
             _ => false,
         }
     }
@@ -124,6 +130,14 @@ impl BitOr for Criteria {
     }
 }
 
+impl Not for Criteria {
+    type Output = Criteria;
+
+    fn not(self) -> Self::Output {
+        Criteria::Not(Box::new(self))
+    }
+}
+
 pub fn scenario<P: Into<Pattern>>(pattern: P) -> Criteria {
     Criteria::Scenario(pattern.into())
 }
@@ -135,3 +149,22 @@ pub fn rule<P: Into<Pattern>>(pattern: P) -> Criteria {
 pub fn feature<P: Into<Pattern>>(pattern: P) -> Criteria {
     Criteria::Feature(pattern.into())
 }
+
+fn strip_at(tag: &str) -> String {
+    tag.strip_prefix('@').unwrap_or(tag).to_string()
+}
+
+/// Matches scenarios with the given tag, including tags set on their rule or feature.
+pub fn scenario_tag(tag: &str) -> Criteria {
+    Criteria::Tag(Context::Scenario, strip_at(tag))
+}
+
+/// Matches rules with the given tag, including tags set on their feature.
+pub fn rule_tag(tag: &str) -> Criteria {
+    Criteria::Tag(Context::Rule, strip_at(tag))
+}
+
+/// Matches features with the given tag.
+pub fn feature_tag(tag: &str) -> Criteria {
+    Criteria::Tag(Context::Feature, strip_at(tag))
+}
@@ -9,17 +9,20 @@
 use std::{
     any::{Any, TypeId},
     collections::HashMap,
-    path::Path,
+    path::{Path, PathBuf},
     rc::Rc,
 };
 use std::{pin::Pin, time::Duration};
 
 use futures::{Future, StreamExt};
-use gherkin::ParseFileError;
+use gherkin::{tagexpr::TagOperation, ParseFileError};
 use regex::Regex;
 
+use crate::event::{FailureKind, PanicInfo, StepEvent};
+use crate::examples::RowLines;
+use crate::rerun::{Locations, RerunCollector};
 use crate::{criteria::Criteria, steps::Steps};
-use crate::{EventHandler, World};
+use crate::{EventHandler, Order, Shard, World};
 
 pub(crate) type LifecycleFuture = Pin<Box<dyn Future<Output = ()>>>;
 
@@ -29,6 +32,24 @@ pub struct LifecycleContext {
     pub feature: Rc<gherkin::Feature>,
     pub rule: Option<Rc<gherkin::Rule>>,
     pub scenario: Option<Rc<gherkin::Scenario>>,
+    /// How the scenario, rule or feature turned out. Only set for `after`
+    /// hooks.
+    pub outcome: Option<Outcome>,
+}
+
+/// How a scenario, rule or feature turned out. A rule or feature has failed
+/// as soon as one of its scenarios has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Skipped,
+    Failed {
+        kind: FailureKind,
+        /// The step which failed, if the failure happened in a step.
+        step: Option<Rc<gherkin::Step>>,
+        /// The panic of the failing step or hook, if it panicked.
+        panic_info: Option<PanicInfo>,
+    },
 }
 
 impl LifecycleContext {
@@ -40,6 +61,24 @@ impl LifecycleContext {
 
 pub type LifecycleFn = fn(LifecycleContext) -> LifecycleFuture;
 
+pub type WorldLifecycleFuture<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+pub type WorldLifecycleFn<W> = for<'a> fn(&'a mut W, LifecycleContext) -> WorldLifecycleFuture<'a>;
+
+pub type StepHookFn<W> = for<'a> fn(
+    Option<&'a mut W>,
+    &'a gherkin::Step,
+    &'a StepEvent,
+    LifecycleContext,
+) -> WorldLifecycleFuture<'a>;
+
+pub type RunHookFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error>>> + 'a>>;
+
+pub type BeforeAllFn = for<'a> fn(&'a mut Context) -> RunHookFuture<'a>;
+
+pub type AfterAllFn = for<'a> fn(&'a Context) -> RunHookFuture<'a>;
+
 pub struct Cucumber<W: World> {
     context: Context,
 
@@ -53,6 +92,14 @@ pub struct Cucumber<W: World> {
     /// step clean-up handling (i.e. to recover panic info)
     step_timeout: Option<Duration>,
 
+    /// If `Some`, enforce an upper bound on the amount of time each attempt
+    /// at a scenario, including its hooks, is allowed to take.
+    scenario_timeout: Option<Duration>,
+
+    /// If `Some`, enforce an upper bound on the amount of time the whole run
+    /// is allowed to take.
+    run_timeout: Option<Duration>,
+
     /// If true, capture stdout and stderr content
     /// during tests.
     enable_capture: bool,
@@ -60,6 +107,39 @@ pub struct Cucumber<W: World> {
     /// If given, filters the scenario which are run
     scenario_filter: Option<Regex>,
 
+    /// If given, only scenarios whose tags match this expression are run
+    tag_filter: Option<TagOperation>,
+
+    /// The maximum number of scenarios allowed to run at the same time.
+    max_concurrent_scenarios: usize,
+
+    /// If true, stop starting new scenarios once one has failed.
+    fail_fast: bool,
+
+    /// How many times a failing scenario is retried by default.
+    retries: u32,
+
+    /// If true, only resolve the steps instead of running them.
+    dry_run: bool,
+
+    /// If true, a step matched by several step functions fails its scenario.
+    fail_on_ambiguous_steps: bool,
+
+    /// The order features and scenarios are run in.
+    order: Order,
+
+    /// If given, only the scenarios of this shard are run.
+    shard: Option<Shard>,
+
+    /// If given, only the scenarios at these locations are run.
+    locations: Option<Locations>,
+
+    /// The lines of the example rows of `features`.
+    row_lines: RowLines,
+
+    /// If given, the locations of failed scenarios are written to this file.
+    rerun_file: Option<PathBuf>,
+
     language: Option<String>,
 
     debug: bool,
@@ -67,6 +147,18 @@ pub struct Cucumber<W: World> {
     before: Vec<(Criteria, LifecycleFn)>,
 
     after: Vec<(Criteria, LifecycleFn)>,
+
+    before_world: Vec<(Criteria, WorldLifecycleFn<W>)>,
+
+    after_world: Vec<(Criteria, WorldLifecycleFn<W>)>,
+
+    before_step: Vec<(Criteria, StepHookFn<W>)>,
+
+    after_step: Vec<(Criteria, StepHookFn<W>)>,
+
+    before_all: Vec<BeforeAllFn>,
+
+    after_all: Vec<AfterAllFn>,
 }
 
 pub struct StepContext {
@@ -111,6 +203,7 @@ impl Context {
         self.data.insert(TypeId::of::<T>(), Box::new(value));
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn add<T: Any>(mut self, value: T) -> Self {
         self.insert(value);
         self
@@ -123,14 +216,33 @@ impl<W: World> Default for Cucumber<W> {
             context: Default::default(),
             steps: Default::default(),
             features: Default::default(),
-            event_handler: Box::new(crate::output::BasicOutput::new(false)),
+            event_handler: Box::new(crate::output::BasicOutput::new(false).for_world::<W>()),
             step_timeout: None,
+            scenario_timeout: None,
+            run_timeout: None,
             enable_capture: true,
             debug: false,
             scenario_filter: None,
+            tag_filter: None,
+            max_concurrent_scenarios: 1,
+            fail_fast: false,
+            retries: 0,
+            dry_run: false,
+            fail_on_ambiguous_steps: false,
+            order: Order::Defined,
+            shard: None,
+            locations: None,
+            row_lines: Default::default(),
+            rerun_file: None,
             language: None,
             before: vec![],
             after: vec![],
+            before_world: vec![],
+            after_world: vec![],
+            before_step: vec![],
+            after_step: vec![],
+            before_all: vec![],
+            after_all: vec![],
         }
     }
 }
@@ -152,12 +264,31 @@ impl<W: World> Cucumber<W> {
             features: Default::default(),
             event_handler: Box::new(event_handler),
             step_timeout: None,
+            scenario_timeout: None,
+            run_timeout: None,
             enable_capture: true,
             debug: false,
             scenario_filter: None,
+            tag_filter: None,
+            max_concurrent_scenarios: 1,
+            fail_fast: false,
+            retries: 0,
+            dry_run: false,
+            fail_on_ambiguous_steps: false,
+            order: Order::Defined,
+            shard: None,
+            locations: None,
+            row_lines: Default::default(),
+            rerun_file: None,
             language: None,
             before: vec![],
             after: vec![],
+            before_world: vec![],
+            after_world: vec![],
+            before_step: vec![],
+            after_step: vec![],
+            before_all: vec![],
+            after_all: vec![],
         }
     }
 
@@ -184,7 +315,7 @@ impl<W: World> Cucumber<W> {
                     std::process::exit(1);
                 }
             })
-            .map(|path| {
+            .flat_map(|path| {
                 let env = match self.language.as_ref() {
                     Some(lang) => gherkin::GherkinEnv::new(lang).unwrap(),
                     None => Default::default(),
@@ -209,7 +340,6 @@ impl<W: World> Cucumber<W> {
                         .collect::<Vec<_>>()
                 }
             })
-            .flatten()
             .collect::<Result<Vec<_>, _>>()
             .unwrap_or_else(|e| match e {
                 ParseFileError::Reading { path, source } => {
@@ -233,7 +363,12 @@ impl<W: World> Cucumber<W> {
 
         features.sort();
 
+        let mut row_lines = RowLines::new();
+        for feature in features.iter() {
+            crate::examples::find_row_lines(feature, &mut row_lines);
+        }
         self.features = features;
+        self.row_lines = row_lines;
         self
     }
 
@@ -246,6 +381,31 @@ impl<W: World> Cucumber<W> {
         self
     }
 
+    /// Enforce an upper bound on the amount of time each attempt at a
+    /// scenario is allowed to take, covering its hooks, `World::new` and all
+    /// of its steps. A scenario can set its own limit with a tag such as
+    /// `@timeout-30s`.
+    ///
+    /// Scenarios exceeding it fail with `FailureKind::ScenarioTimedOut`. Their
+    /// after hooks still run, with a few seconds of their own to finish in.
+    pub fn scenario_timeout(mut self, scenario_timeout: Duration) -> Self {
+        self.scenario_timeout = Some(scenario_timeout);
+        self
+    }
+
+    /// Enforce an upper bound on the amount of time the whole run is allowed
+    /// to take.
+    ///
+    /// Scenarios still running when it expires fail with
+    /// `FailureKind::RunTimedOut`, and those not yet started are counted in
+    /// `RunResult::scenarios.not_run`. Features that had not started by then
+    /// are counted in `RunResult::features.not_run`. After hooks and world
+    /// teardown of running scenarios get a few seconds past it to finish.
+    pub fn run_timeout(mut self, run_timeout: Duration) -> Self {
+        self.run_timeout = Some(run_timeout);
+        self
+    }
+
     /// If true, capture stdout and stderr content
     /// during tests.
     pub fn enable_capture(mut self, enable_capture: bool) -> Self {
@@ -259,6 +419,132 @@ impl<W: World> Cucumber<W> {
         self
     }
 
+    /// Only run scenarios matching a Cucumber tag expression, such as
+    /// `@smoke and not (@wip or @slow)`.
+    ///
+    /// Tags are inherited, so a scenario is matched against its own tags
+    /// along with those of its feature, rule and `Examples` block.
+    ///
+    /// Panics if `expr` is not a valid tag expression.
+    pub fn filter_tags(mut self, expr: &str) -> Self {
+        let op = expr
+            .parse::<TagOperation>()
+            .unwrap_or_else(|e| panic!("Error parsing tag expression '{}': {}", expr, e));
+        self.tag_filter = Some(op);
+        self
+    }
+
+    /// Run up to `max` scenarios at the same time. Defaults to 1, which runs
+    /// every scenario one after another.
+    ///
+    /// Events are still reported in the order the scenarios appear in the
+    /// feature files; events of scenarios that finish early are held back
+    /// until every scenario before them has been reported.
+    ///
+    /// Above 1, features are run concurrently as well, so the `before` and
+    /// `after` hooks of one feature or rule may run while scenarios of
+    /// another are still going.
+    ///
+    /// Scenarios tagged `@serial`, directly or through their rule or feature,
+    /// never run alongside any other scenario or feature or rule hook.
+    ///
+    /// Output capture redirects the process-wide stdout and stderr, so above
+    /// 1 it is turned off, whatever `enable_capture` says.
+    pub fn max_concurrent_scenarios(mut self, max: usize) -> Self {
+        self.max_concurrent_scenarios = max.max(1);
+        self
+    }
+
+    /// If true, stop starting new scenarios as soon as one fails.
+    ///
+    /// Scenarios that are already running are finished along with the
+    /// `after` hooks of their rules and features. Scenarios which were never
+    /// started are counted in `RunResult::scenarios.not_run`, and features
+    /// that were never started in `RunResult::features.not_run`.
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Retry a failing scenario up to `retries` times, each time with a new
+    /// `World`. Individual scenarios can override this with a tag such as
+    /// `@retry-3`.
+    ///
+    /// Scenarios which only pass after a retry are counted as flaky.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// If true, resolve every step of the selected scenarios without running
+    /// anything: no world is created and no hooks are run.
+    ///
+    /// Resolved steps are reported as skipped. A scenario with an undefined
+    /// or ambiguous step fails with `FailureKind::Undefined` or
+    /// `FailureKind::Ambiguous`, failing the run.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// If true, a step matched by more than one step function fails its
+    /// scenario with `FailureKind::Ambiguous`. Otherwise it is skipped, like
+    /// an undefined step. Either way it is reported as
+    /// `StepEvent::Ambiguous`, listing the matching patterns.
+    pub fn fail_on_ambiguous_steps(mut self, fail: bool) -> Self {
+        self.fail_on_ambiguous_steps = fail;
+        self
+    }
+
+    /// Set the order features, rules and scenarios are run in. Defaults to
+    /// `Order::Defined`.
+    ///
+    /// A random order helps to find scenarios depending on each other
+    /// through shared state. Its seed is reported in `RunResult::seed`, so
+    /// that a failing order can be replayed with `Order::Random(seed)`.
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Only run the scenarios of `shard`, to split the suite across several
+    /// machines. Running every shard of the same total runs each scenario
+    /// exactly once.
+    pub fn shard(mut self, shard: Shard) -> Self {
+        self.shard = Some(shard);
+        self
+    }
+
+    /// Once the run is over, write the `path:line` of every failed scenario,
+    /// or of its example row, to `path`, for `rerun_from` to run them again.
+    /// The file is left empty if nothing failed.
+    pub fn rerun_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.rerun_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Only run the scenarios listed in the rerun file at `path`, as written
+    /// by `rerun_file`. This replaces the features added so far with those
+    /// listed in the file.
+    pub fn rerun_from<P: AsRef<Path>>(self, path: P) -> Self {
+        let locations = crate::rerun::read(path.as_ref()).unwrap_or_else(|e| {
+            eprintln!("Error reading rerun file '{}':", path.as_ref().display());
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+
+        let mut paths = locations
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+
+        let mut s = self.features(paths);
+        s.locations = Some(locations);
+        s
+    }
+
     /// Call this to incorporate command line options into the configuration.
     pub fn cli(self) -> Self {
         let opts = crate::cli::make_app();
@@ -268,6 +554,10 @@ impl<W: World> Cucumber<W> {
             s = s.scenario_regex(&re);
         }
 
+        if let Some(op) = opts.tag_filter {
+            s.tag_filter = Some(op);
+        }
+
         if opts.nocapture {
             s = s.enable_capture(false);
         }
@@ -276,6 +566,38 @@ impl<W: World> Cucumber<W> {
             s = s.debug(true);
         }
 
+        if opts.fail_fast {
+            s = s.fail_fast(true);
+        }
+
+        if let Some(order) = opts.order {
+            s = s.order(order);
+        }
+
+        if let Some(shard) = opts.shard {
+            s = s.shard(shard);
+        }
+
+        if let Some(path) = opts.rerun {
+            s = s.rerun_from(path);
+        }
+
+        if let Some(path) = opts.rerun_file {
+            s = s.rerun_file(path);
+        }
+
+        if opts.dry_run {
+            s = s.dry_run(true);
+        }
+
+        if let Some(retries) = opts.retries {
+            s = s.retries(retries);
+        }
+
+        if let Some(concurrency) = opts.concurrency {
+            s = s.max_concurrent_scenarios(concurrency);
+        }
+
         s
     }
 
@@ -303,9 +625,61 @@ impl<W: World> Cucumber<W> {
         self
     }
 
+    /// Add a hook run for every scenario matching `criteria` once its world
+    /// has been created, before its first step.
+    pub fn before_world(mut self, criteria: Criteria, handler: WorldLifecycleFn<W>) -> Self {
+        self.before_world.push((criteria, handler));
+        self
+    }
+
+    /// Add a hook run for every scenario matching `criteria` after its last
+    /// step, before the `after` hooks.
+    ///
+    /// Steps take the world by value, so the hook is not run when a step
+    /// panicked or timed out and took the world with it. Inspecting the
+    /// world a step failed with is not supported; the hook only sees worlds
+    /// that made it to the end, such as those of scenarios failed by a hook.
+    /// Use the outcome passed to the `after` hooks to report on failures.
+    pub fn after_world(mut self, criteria: Criteria, handler: WorldLifecycleFn<W>) -> Self {
+        self.after_world.push((criteria, handler));
+        self
+    }
+
+    /// Add a hook run before every step of the scenarios matching
+    /// `criteria`, including background steps. It receives the world and
+    /// `StepEvent::Starting`.
+    pub fn before_step(mut self, criteria: Criteria, handler: StepHookFn<W>) -> Self {
+        self.before_step.push((criteria, handler));
+        self
+    }
+
+    /// Add a hook run after every step of the scenarios matching `criteria`,
+    /// including background steps, with the outcome of the step.
+    ///
+    /// The world is `None` when the step panicked or timed out and took the
+    /// world with it.
+    pub fn after_step(mut self, criteria: Criteria, handler: StepHookFn<W>) -> Self {
+        self.after_step.push((criteria, handler));
+        self
+    }
+
+    /// Add a hook run once before the first feature, which may add to the
+    /// shared `Context`. If it fails, no features are run.
+    pub fn before_all(mut self, handler: BeforeAllFn) -> Self {
+        self.before_all.push(handler);
+        self
+    }
+
+    /// Add a hook run once after the last feature, even if a `before_all`
+    /// hook failed.
+    pub fn after_all(mut self, handler: AfterAllFn) -> Self {
+        self.after_all.push(handler);
+        self
+    }
+
     /// Enable printing stdout and stderr for every step, regardless of error state.
     pub fn debug(mut self, value: bool) -> Self {
-        self.event_handler = Box::new(crate::output::BasicOutput::new(value));
+        self.event_handler = Box::new(crate::output::BasicOutput::new(value).for_world::<W>());
         self.debug = value;
         self
     }
@@ -317,22 +691,60 @@ impl<W: World> Cucumber<W> {
 
     /// Run and report number of errors if any
     pub async fn run(mut self) -> crate::runner::RunResult {
+        let seed = match self.order {
+            Order::Defined => None,
+            Order::Random(seed) => {
+                crate::order::shuffle(&mut self.features, seed);
+                Some(seed)
+            }
+        };
+
         let runner = crate::runner::Runner::new(
             Rc::new(self.context),
             self.steps.steps,
             Rc::new(self.features),
-            self.step_timeout,
-            self.enable_capture,
-            self.scenario_filter,
-            self.before,
-            self.after,
+            crate::runner::RunnerOptions {
+                step_timeout: self.step_timeout,
+                enable_capture: self.enable_capture,
+                scenario_filter: self.scenario_filter,
+                tag_filter: self.tag_filter,
+                max_concurrent_scenarios: self.max_concurrent_scenarios,
+                fail_fast: self.fail_fast,
+                retries: self.retries,
+                scenario_timeout: self.scenario_timeout,
+                run_timeout: self.run_timeout,
+                dry_run: self.dry_run,
+                fail_on_ambiguous_steps: self.fail_on_ambiguous_steps,
+                seed,
+                shard: self.shard,
+                locations: self.locations,
+                row_lines: self.row_lines,
+            },
+            crate::runner::Hooks {
+                before: self.before,
+                after: self.after,
+                before_world: self.before_world,
+                after_world: self.after_world,
+                before_step: self.before_step,
+                after_step: self.after_step,
+                before_all: self.before_all,
+                after_all: self.after_all,
+            },
         );
         let mut stream = runner.run();
+        let mut rerun = RerunCollector::default();
 
         while let Some(event) = stream.next().await {
             self.event_handler.handle_event(&event);
+            rerun.handle_event(&event);
 
             if let crate::event::CucumberEvent::Finished(result) = event {
+                if let Some(path) = self.rerun_file {
+                    if let Err(e) = rerun.write(&path) {
+                        eprintln!("Error writing rerun file '{}':", path.display());
+                        eprintln!("{}", e);
+                    }
+                }
                 return result;
             }
         }
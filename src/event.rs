@@ -73,9 +73,14 @@ impl PanicInfo {
 /// `World` state.
 pub(crate) enum TestEvent<W> {
     Unimplemented,
+    Ambiguous(Vec<String>),
     Skipped,
     Success(W, CapturedOutput),
     Failure(StepFailureKind),
+    /// The step did not run to completion: either the scenario or run
+    /// deadline passed while it was running, or a `before_step` hook failed
+    /// so it never started.
+    Interrupted(FailureKind),
 }
 
 /// Event specific to a particular [Step](https://cucumber.io/docs/gherkin/reference/#step)
@@ -83,6 +88,9 @@ pub(crate) enum TestEvent<W> {
 pub enum StepEvent {
     Starting,
     Unimplemented,
+    /// More than one step function matches the step, so none was run.
+    /// Carries the patterns of the matching step functions.
+    Ambiguous(Vec<String>),
     Skipped,
     Passed(CapturedOutput),
     Failed(StepFailureKind),
@@ -94,6 +102,14 @@ pub enum ScenarioEvent {
     Starting(ExampleValues),
     Background(Rc<gherkin::Step>, StepEvent),
     Step(Rc<gherkin::Step>, StepEvent),
+    /// The previous attempt failed and the scenario is run again with a new
+    /// `World`. Carries the number of this retry, starting from 1.
+    Retrying(u32),
+    /// `World::teardown` returned an error. Carries the error message
+    /// followed by the messages of each of its sources.
+    TeardownFailed(Vec<String>),
+    /// A hook run for this scenario or around one of its steps.
+    Hook(HookType, HookEvent),
     Skipped,
     Passed,
     Failed(FailureKind),
@@ -103,6 +119,7 @@ pub enum ScenarioEvent {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RuleEvent {
     Starting,
+    Hook(HookType, HookEvent),
     Scenario(Rc<gherkin::Scenario>, ScenarioEvent),
     Skipped,
     Passed,
@@ -113,23 +130,83 @@ pub enum RuleEvent {
 #[derive(Debug, Clone)]
 pub enum FeatureEvent {
     Starting,
+    Hook(HookType, HookEvent),
     Scenario(Rc<gherkin::Scenario>, ScenarioEvent),
     Rule(Rc<gherkin::Rule>, RuleEvent),
     Finished,
+    /// The feature was never started, because fail-fast or the run timeout
+    /// had stopped the run by the time its turn came.
+    NotRun,
 }
 
 /// Top-level cucumber run event.
 #[derive(Debug, Clone)]
 pub enum CucumberEvent {
     Starting,
+    /// A `before_all` or `after_all` hook.
+    Hook(HookType, HookEvent),
     Feature(Rc<gherkin::Feature>, FeatureEvent),
     Finished(crate::runner::RunResult),
 }
 
+/// Whether a hook runs before or after the part of the run it surrounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookType {
+    Before,
+    After,
+    BeforeStep,
+    AfterStep,
+}
+
+/// Event specific to a lifecycle hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookEvent {
+    Starting,
+    Passed,
+    Failed(HookFailureKind),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookFailureKind {
+    /// The hook returned an error. Carries the error message followed by the
+    /// messages of each of its sources.
+    Error(Vec<String>),
+    Panic(CapturedOutput, PanicInfo),
+    TimedOut,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FailureKind {
+    /// A step took longer than the step timeout.
     TimedOut,
+    /// The scenario took longer than the scenario timeout.
+    ScenarioTimedOut,
+    /// The whole run took longer than the run timeout.
+    RunTimedOut,
     Panic,
+    /// `World::new` returned an error. Carries the error message followed
+    /// by the messages of each of its sources.
+    WorldInit(Vec<String>),
+    /// The world's teardown returned an error.
+    Teardown,
+    /// A lifecycle hook of the scenario, or of its rule or feature, failed.
+    Hook,
+    /// A step has no matching step function. Only reported by dry runs,
+    /// where it is the only way for a scenario to fail.
+    Undefined,
+    /// More than one step function matches a step. Only reported when
+    /// ambiguous steps are configured to fail, and by dry runs.
+    Ambiguous,
+}
+
+impl FailureKind {
+    /// Whether this failure was caused by a time limit.
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            FailureKind::TimedOut | FailureKind::ScenarioTimedOut | FailureKind::RunTimedOut
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -1,33 +1,80 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The lines of the rows of every `Examples` table, by feature file path and
+/// the line the table starts on.
+pub(crate) type RowLines = HashMap<(PathBuf, usize), Vec<usize>>;
+
 /// Content derived from a gherkin `Examples` table. Contains the table's keys
 /// and for values drawn from a single row.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExampleValues {
     pub keys: Vec<String>,
     pub values: Vec<String>,
+    /// The name of the `Examples` block the row comes from, if it has one.
+    pub name: Option<String>,
+    /// The tags of the `Examples` block the row comes from.
+    pub tags: Vec<String>,
+    /// The line of the row in its feature file, if the file could be read.
+    pub line: Option<usize>,
 }
 
 impl ExampleValues {
+    /// Expands every row of every given `Examples` block, in order.
+    ///
     /// When no examples exist a vector with one empty ExampleValues struct is returned.
-    pub fn from_examples(examples: &Option<gherkin::Examples>) -> Vec<ExampleValues> {
-        match examples {
-            Some(examples) => {
-                let mut rows = Vec::with_capacity(examples.table.rows.len());
-                for row_index in 1..examples.table.rows.len() {
-                    rows.push(ExampleValues::new(
-                        &examples.table.rows.first().unwrap().to_vec(),
-                        &examples.table.rows.get(row_index).unwrap().to_vec(),
-                    ))
-                }
-                rows
+    pub fn from_examples<'a, I>(examples: I) -> Vec<ExampleValues>
+    where
+        I: IntoIterator<Item = &'a gherkin::Examples>,
+    {
+        ExampleValues::from_examples_in(examples, None, &RowLines::new())
+    }
+
+    /// Like `from_examples`, also giving each row its line in the feature
+    /// file at `path`, as found in `lines`.
+    pub(crate) fn from_examples_in<'a, I>(
+        examples: I,
+        path: Option<&Path>,
+        lines: &RowLines,
+    ) -> Vec<ExampleValues>
+    where
+        I: IntoIterator<Item = &'a gherkin::Examples>,
+    {
+        let mut rows = vec![];
+        let mut has_examples = false;
+        for examples in examples {
+            has_examples = true;
+            let table = match examples.table {
+                Some(ref table) => table,
+                None => continue,
+            };
+            let lines = path.and_then(|path| lines.get(&(path.to_path_buf(), table.position.line)));
+            for row_index in 1..table.rows.len() {
+                let mut row = ExampleValues::new(
+                    &table.rows.first().unwrap().to_vec(),
+                    &table.rows.get(row_index).unwrap().to_vec(),
+                );
+                row.name = examples.name.clone();
+                row.tags = examples.tags.clone();
+                row.line = lines.map(|lines| lines[row_index]);
+                rows.push(row)
             }
-            None => vec![ExampleValues::empty()],
+        }
+
+        if has_examples {
+            rows
+        } else {
+            vec![ExampleValues::empty()]
         }
     }
 
-    pub fn new(keys: &Vec<String>, values: &Vec<String>) -> ExampleValues {
+    pub fn new(keys: &[String], values: &[String]) -> ExampleValues {
         ExampleValues {
-            keys: keys.into_iter().map(|val| format!("<{}>", val)).collect(),
+            keys: keys.iter().map(|val| format!("<{}>", val)).collect(),
             values: values.to_vec(),
+            name: None,
+            tags: vec![],
+            line: None,
         }
     }
 
@@ -35,6 +82,9 @@ impl ExampleValues {
         ExampleValues {
             keys: vec![],
             values: vec![],
+            name: None,
+            tags: vec![],
+            line: None,
         }
     }
 
@@ -52,7 +102,29 @@ impl ExampleValues {
         modified
     }
 
-    pub fn to_string(&self) -> String {
+    /// Returns a copy of `step` with the example values substituted into its
+    /// text, docstring and data table cells.
+    pub fn insert_into_step(&self, step: &gherkin::Step) -> gherkin::Step {
+        let mut step = step.clone();
+        if self.is_empty() {
+            return step;
+        }
+
+        step.value = self.insert_values(&step.value);
+        if let Some(ref mut docstring) = step.docstring {
+            *docstring = self.insert_values(docstring);
+        }
+        if let Some(ref mut table) = step.table {
+            for cell in table.rows.iter_mut().flat_map(|row| row.iter_mut()) {
+                *cell = self.insert_values(cell);
+            }
+        }
+        step
+    }
+}
+
+impl std::fmt::Display for ExampleValues {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut values = Vec::with_capacity(self.keys.len());
         for index in 0..self.keys.len() {
             values.push(format!(
@@ -61,6 +133,55 @@ impl ExampleValues {
                 self.values.get(index).unwrap_or(&String::new())
             ));
         }
-        values.join(", ")
+        write!(f, "{}", values.join(", "))
+    }
+}
+
+/// Finds the lines of the example rows of `feature`, reading its file once.
+pub(crate) fn find_row_lines(feature: &gherkin::Feature, lines: &mut RowLines) {
+    let path = match feature.path {
+        Some(ref path) => path,
+        None => return,
+    };
+    let tables = feature
+        .scenarios
+        .iter()
+        .chain(feature.rules.iter().flat_map(|rule| rule.scenarios.iter()))
+        .flat_map(|scenario| scenario.examples.iter())
+        .filter_map(|examples| examples.table.as_ref())
+        .collect::<Vec<_>>();
+    if tables.is_empty() {
+        return;
+    }
+
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+    for table in tables {
+        if let Some(rows) = row_lines(table, &source) {
+            lines.insert((path.clone(), table.position.line), rows);
+        }
+    }
+}
+
+/// The lines of the rows of `table` in `source`, header first.
+///
+/// The parser only records where a table starts, so the rows are found by
+/// reading on from there, past any blank lines and comments between them.
+fn row_lines(table: &gherkin::Table, source: &str) -> Option<Vec<usize>> {
+    let start = table.position.line.checked_sub(1)?;
+    let mut lines = Vec::with_capacity(table.rows.len());
+    for (index, text) in source.lines().enumerate().skip(start) {
+        let text = text.trim_start();
+        if text.starts_with('|') {
+            lines.push(index + 1);
+            if lines.len() == table.rows.len() {
+                return Some(lines);
+            }
+        } else if !text.is_empty() && !text.starts_with('#') {
+            break;
+        }
     }
+    None
 }
@@ -20,14 +20,19 @@ mod macros;
 
 mod cli;
 mod collection;
+mod concurrency;
 pub mod criteria;
 mod cucumber;
 pub mod event;
 mod examples;
+mod order;
 pub mod output;
 mod regex;
+mod rerun;
 pub(crate) mod runner;
+mod shard;
 mod steps;
+mod tags;
 
 #[cfg(feature = "macros")]
 #[doc(hidden)]
@@ -37,9 +42,11 @@ pub mod private;
 pub use async_trait::async_trait;
 pub use futures;
 
-pub use cucumber::{Context, Cucumber, StepContext};
+pub use cucumber::{Context, Cucumber, LifecycleContext, Outcome, StepContext};
 pub use examples::ExampleValues;
+pub use order::Order;
 pub use runner::RunResult;
+pub use shard::Shard;
 pub use steps::Steps;
 
 #[cfg(feature = "macros")]
@@ -67,8 +74,26 @@ pub trait World: Sized + 'static {
     type Error: std::error::Error;
 
     async fn new() -> Result<Self, Self::Error>;
+
+    /// Returns async cleanup to run once the scenario using this world is
+    /// over, after its last step and its `after` hooks.
+    ///
+    /// It is asked for once more when the scenario is over, so it sees the
+    /// state the steps left behind, such as a child process they spawned.
+    /// Steps take the world by value though, so a step that panics or times
+    /// out drops it before then. In that case the cleanup asked for as soon
+    /// as the world was created is run instead, which therefore has to own
+    /// whatever it tears down, e.g. a shared handle to a connection. Either
+    /// way it is awaited whether the scenario passed, failed or timed out,
+    /// though past the run timeout only for a few seconds.
+    fn teardown(&self) -> Option<Teardown<Self::Error>> {
+        None
+    }
 }
 
+/// Async cleanup of a `World`, see `World::teardown`.
+pub type Teardown<E> = std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), E>>>>;
+
 /// During test runs, a `Cucumber` instance notifies its
 /// associated `EventHandler` implementation about the
 /// key occurrences in the test lifecycle.
@@ -79,7 +104,7 @@ pub trait EventHandler: 'static {
     fn handle_event(&mut self, event: &event::CucumberEvent);
 }
 
-pub type PanicError = Box<(dyn std::any::Any + Send + 'static)>;
+pub type PanicError = Box<dyn std::any::Any + Send + 'static>;
 pub enum TestError {
     TimedOut,
     PanicError(PanicError),
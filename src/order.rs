@@ -0,0 +1,85 @@
+// Copyright (c) 2018-2021  Brendan Molloy <brendan@bbqsrc.net>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The order features and scenarios are run in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// Features sorted by path, scenarios in the order of their file.
+    #[default]
+    Defined,
+    /// Features, rules and scenarios shuffled with the given seed. The same
+    /// seed always gives the same order.
+    Random(u64),
+}
+
+impl Order {
+    /// A random order with a seed drawn from the clock.
+    pub fn random() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Order::Random(SplitMix64(nanos).next())
+    }
+}
+
+/// Parses `defined`, `random` or `random:SEED`.
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "defined" => Ok(Order::Defined),
+            None if s == "random" => Ok(Order::random()),
+            Some(("random", seed)) => seed
+                .parse()
+                .map(Order::Random)
+                .map_err(|_| format!("invalid seed '{}'", seed)),
+            _ => Err(format!(
+                "unknown order '{}', expected 'defined', 'random' or 'random:SEED'",
+                s
+            )),
+        }
+    }
+}
+
+/// A small, fast generator which is good enough to shuffle with.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Shuffles the features, along with the scenarios and rules of each.
+pub(crate) fn shuffle(features: &mut [gherkin::Feature], seed: u64) {
+    let mut rng = SplitMix64(seed);
+    rng.shuffle(features);
+    for feature in features.iter_mut() {
+        rng.shuffle(&mut feature.scenarios);
+        rng.shuffle(&mut feature.rules);
+        for rule in feature.rules.iter_mut() {
+            rng.shuffle(&mut rule.scenarios);
+        }
+    }
+}
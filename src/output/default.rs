@@ -10,7 +10,10 @@ use std::io::Write;
 use std::path::PathBuf;
 use std::rc::Rc;
 
-use crate::event::{CapturedOutput, StepFailureKind};
+use crate::event::{
+    CapturedOutput, FailureKind, HookEvent, HookFailureKind, HookType, StepFailureKind,
+};
+use crate::output::Snippet;
 use crate::runner::{RunResult, Stats};
 use crate::{
     event::{CucumberEvent, RuleEvent, ScenarioEvent, StepEvent},
@@ -18,22 +21,16 @@ use crate::{
 };
 use gherkin::{Feature, LineCol, Rule, Scenario, Step};
 
+#[derive(Default)]
 pub struct BasicOutput {
     debug: bool,
     step_started: bool,
     pending_feature_print_info: Option<(String, String)>,
     printed_feature_start: bool,
-}
-
-impl Default for BasicOutput {
-    fn default() -> BasicOutput {
-        BasicOutput {
-            debug: false,
-            step_started: false,
-            pending_feature_print_info: None,
-            printed_feature_start: false,
-        }
-    }
+    /// The type name of the world, for the snippets of undefined steps.
+    world: Option<&'static str>,
+    /// Snippets for each distinct undefined step, printed in the summary.
+    snippets: Vec<Snippet>,
 }
 
 fn wrap_with_comment(s: &str, c: &str, indent: &str) -> String {
@@ -63,6 +60,14 @@ impl BasicOutput {
         }
     }
 
+    /// Name the world type `W` in the snippets of undefined steps.
+    pub(crate) fn for_world<W>(mut self) -> Self {
+        let name = std::any::type_name::<W>();
+        let name = name.split('<').next().unwrap_or(name);
+        self.world = name.rsplit("::").next();
+        self
+    }
+
     fn relpath(&self, target: Option<&std::path::PathBuf>) -> String {
         let target = match target {
             Some(v) => v,
@@ -71,7 +76,7 @@ impl BasicOutput {
         let target = target.canonicalize().expect("invalid target path");
         pathdiff::diff_paths(
             &target,
-            &std::env::current_dir().expect("invalid current directory"),
+            std::env::current_dir().expect("invalid current directory"),
         )
         .expect("invalid target path")
         .to_string_lossy()
@@ -92,7 +97,8 @@ impl BasicOutput {
                 }
             }
 
-            let formatted_row_fields: Vec<Vec<String>> = (&table.rows)
+            let formatted_row_fields: Vec<Vec<String>> = table
+                .rows
                 .iter()
                 .map(|row| {
                     row.iter()
@@ -194,7 +200,7 @@ impl BasicOutput {
             );
 
             self.writeln(
-                &textwrap::indent(
+                textwrap::indent(
                     &textwrap::fill(&output.out, textwrap::termwidth().saturating_sub(4)),
                     "  ",
                 )
@@ -216,7 +222,7 @@ impl BasicOutput {
             );
 
             self.writeln(
-                &textwrap::indent(
+                textwrap::indent(
                     &textwrap::fill(&output.err, textwrap::termwidth().saturating_sub(4)),
                     "  ",
                 )
@@ -255,27 +261,50 @@ impl BasicOutput {
 
         match event {
             StepEvent::Starting => {
+                self.writeln_cmt(&msg, &cmt, indent, termcolor::Color::White, false);
+                self.print_step_extras(step);
+                self.step_started = true;
+            }
+            StepEvent::Unimplemented => {
                 self.writeln_cmt(
-                    &format!("{}", msg),
+                    &format!("- {}", msg),
                     &cmt,
                     indent,
-                    termcolor::Color::White,
+                    termcolor::Color::Cyan,
                     false,
                 );
-                self.print_step_extras(&*step);
-                self.step_started = true;
+                self.print_step_extras(step);
+                self.write(&format!("{}  ⚡ ", indent), termcolor::Color::Yellow, false);
+                println!("Not yet implemented (skipped)");
+
+                let snippet = Snippet::new(step);
+                let is_new = !self.snippets.iter().any(|s| {
+                    s.ty == snippet.ty
+                        && s.regex.as_ref().unwrap_or(&s.text)
+                            == snippet.regex.as_ref().unwrap_or(&snippet.text)
+                });
+                if is_new {
+                    self.snippets.push(snippet);
+                }
             }
-            StepEvent::Unimplemented => {
+            StepEvent::Ambiguous(patterns) => {
                 self.writeln_cmt(
                     &format!("- {}", msg),
                     &cmt,
                     indent,
-                    termcolor::Color::Cyan,
+                    termcolor::Color::Magenta,
                     false,
                 );
-                self.print_step_extras(&*step);
+                self.print_step_extras(step);
                 self.write(&format!("{}  ⚡ ", indent), termcolor::Color::Yellow, false);
-                println!("Not yet implemented (skipped)");
+                println!("Ambiguous step, matched by:");
+                for pattern in patterns {
+                    self.writeln(
+                        &format!("{}     {}", indent, pattern),
+                        termcolor::Color::Magenta,
+                        false,
+                    );
+                }
             }
             StepEvent::Skipped => {
                 self.writeln_cmt(
@@ -285,7 +314,7 @@ impl BasicOutput {
                     termcolor::Color::Cyan,
                     false,
                 );
-                self.print_step_extras(&*step);
+                self.print_step_extras(step);
             }
             StepEvent::Passed(output) => {
                 self.writeln_cmt(
@@ -295,7 +324,7 @@ impl BasicOutput {
                     termcolor::Color::Green,
                     false,
                 );
-                self.print_step_extras(&*step);
+                self.print_step_extras(step);
                 if self.debug {
                     self.print_captured(output, termcolor::Color::Cyan);
                 }
@@ -308,7 +337,7 @@ impl BasicOutput {
                     termcolor::Color::Red,
                     false,
                 );
-                self.print_step_extras(&*step);
+                self.print_step_extras(step);
                 self.writeln_cmt(
                     &format!(
                         "{:—<1$}",
@@ -323,7 +352,7 @@ impl BasicOutput {
                     true,
                 );
                 self.writeln(
-                    &textwrap::indent(
+                    textwrap::indent(
                         &textwrap::fill(
                             &panic_info.payload,
                             textwrap::termwidth().saturating_sub(4),
@@ -344,7 +373,7 @@ impl BasicOutput {
                     termcolor::Color::Red,
                     false,
                 );
-                self.print_step_extras(&*step);
+                self.print_step_extras(step);
                 self.writeln_cmt(
                     &format!(
                         "{:—<1$}",
@@ -372,12 +401,15 @@ impl BasicOutput {
                 let cmt = self.file_line_col(feature.path.as_ref(), scenario.position);
                 let text = if example_values.is_empty() {
                     format!("{}: {} ", &scenario.keyword, &scenario.name)
+                } else if let Some(ref name) = example_values.name {
+                    format!(
+                        "{}: {}\n => {}: {}",
+                        &scenario.keyword, &scenario.name, name, example_values,
+                    )
                 } else {
                     format!(
                         "{}: {}\n => {}",
-                        &scenario.keyword,
-                        &scenario.name,
-                        example_values.to_string(),
+                        &scenario.keyword, &scenario.name, example_values,
                     )
                 };
                 let indent = if rule.is_some() { "  " } else { " " };
@@ -389,13 +421,119 @@ impl BasicOutput {
             ScenarioEvent::Step(step, event) => {
                 self.handle_step(feature, rule, scenario, step, event, false)
             }
+            ScenarioEvent::Failed(kind @ FailureKind::ScenarioTimedOut)
+            | ScenarioEvent::Failed(kind @ FailureKind::RunTimedOut) => {
+                let indent = if rule.is_some() { "   " } else { "  " };
+                let msg = if *kind == FailureKind::ScenarioTimedOut {
+                    "[!] Scenario timed out"
+                } else {
+                    "[!] Run timed out"
+                };
+                self.writeln(&format!("{}{}", indent, msg), termcolor::Color::Red, true);
+            }
+            ScenarioEvent::Failed(FailureKind::WorldInit(chain)) => {
+                let indent = if rule.is_some() { "   " } else { "  " };
+                self.print_error_chain(indent, "Failed to create the world", chain);
+            }
+            ScenarioEvent::Hook(kind, event) => {
+                let indent = if rule.is_some() { "   " } else { "  " };
+                self.handle_hook(indent, hook_name(*kind), event);
+            }
+            ScenarioEvent::TeardownFailed(chain) => {
+                let indent = if rule.is_some() { "   " } else { "  " };
+                self.print_error_chain(indent, "Failed to tear down the world", chain);
+            }
+            ScenarioEvent::Retrying(retry) => {
+                let indent = if rule.is_some() { "   " } else { "  " };
+                self.writeln(
+                    &format!("{}↻ Retrying scenario (retry {})", indent, retry),
+                    termcolor::Color::Yellow,
+                    true,
+                );
+            }
             _ => {}
         }
     }
 
+    fn handle_hook(&mut self, indent: &str, name: &str, event: &HookEvent) {
+        let failure = match event {
+            HookEvent::Failed(failure) => failure,
+            _ => return,
+        };
+
+        // Keep the line of a step whose hook failed around.
+        self.step_started = false;
+
+        match failure {
+            HookFailureKind::Error(chain) => {
+                self.print_error_chain(indent, &format!("{} hook failed", name), chain);
+            }
+            HookFailureKind::Panic(output, panic_info) => {
+                self.writeln_cmt(
+                    &format!(
+                        "{:—<1$}",
+                        format!("[!] {} hook failed: ", name),
+                        textwrap::termwidth()
+                            .saturating_sub(panic_info.location.to_string().chars().count())
+                            .saturating_sub(6),
+                    ),
+                    &panic_info.location.to_string(),
+                    "———— ",
+                    termcolor::Color::Red,
+                    true,
+                );
+                self.writeln(
+                    textwrap::indent(
+                        &textwrap::fill(
+                            &panic_info.payload,
+                            textwrap::termwidth().saturating_sub(4),
+                        ),
+                        "  ",
+                    )
+                    .trim_end(),
+                    termcolor::Color::Red,
+                    false,
+                );
+                self.print_captured(output, termcolor::Color::Red);
+            }
+            HookFailureKind::TimedOut => {
+                self.writeln(
+                    &format!("{}[!] {} hook timed out", indent, name),
+                    termcolor::Color::Red,
+                    true,
+                );
+            }
+        }
+    }
+
+    fn print_error_chain(&mut self, indent: &str, title: &str, chain: &[String]) {
+        self.writeln(
+            &format!("{}✘ {}", indent, title),
+            termcolor::Color::Red,
+            true,
+        );
+        for (n, message) in chain.iter().enumerate() {
+            let prefix = if n == 0 { "" } else { "caused by: " };
+            self.writeln(
+                textwrap::indent(
+                    &textwrap::fill(
+                        &format!("{}{}", prefix, message),
+                        textwrap::termwidth().saturating_sub(4),
+                    ),
+                    "  ",
+                )
+                .trim_end(),
+                termcolor::Color::Red,
+                false,
+            );
+        }
+    }
+
     fn handle_rule(&mut self, feature: &Rc<Feature>, rule: &Rc<Rule>, event: &RuleEvent) {
         if let RuleEvent::Scenario(scenario, evt) = event {
             self.handle_scenario(feature, Some(rule), scenario, evt)
+        } else if let RuleEvent::Hook(kind, evt) = event {
+            self.handle_hook("  ", hook_name(*kind), evt)
         } else if *event == RuleEvent::Starting {
             let cmt = self.file_line_col(feature.path.as_ref(), rule.position);
             self.writeln_cmt(
@@ -429,20 +567,63 @@ impl BasicOutput {
         }
 
         cprint!(bold Green, "{} passed", stats.passed);
+
+        if stats.flaky > 0 {
+            cprint!(bold White, ", ");
+            cprint!(bold Yellow, "{} flaky", stats.flaky);
+        }
+
+        if stats.not_run > 0 {
+            cprint!(bold White, ", ");
+            cprint!(bold Yellow, "{} not run", stats.not_run);
+        }
+
         cprintln!(bold White, ")");
     }
 
+    fn print_snippets(&self) {
+        if self.snippets.is_empty() {
+            return;
+        }
+
+        cprintln!(bold termcolor::Color::Yellow, "\n[Undefined steps]");
+        println!("You can implement the undefined steps with these snippets:");
+        for snippet in self.snippets.iter() {
+            println!("\n{}", snippet.builder());
+        }
+
+        println!("\nOr with the attribute macros:");
+        let world = self.world.unwrap_or("MyWorld");
+        for snippet in self.snippets.iter() {
+            println!("\n{}", snippet.attribute(world));
+        }
+    }
+
     fn print_finish(&self, result: &RunResult) {
         use termcolor::Color::*;
 
         cprintln!(bold Blue, "[Summary]");
-        cprintln!(bold White, "{} features", result.features.total);
+        cprint!(bold White, "{} features", result.features.total);
+        if result.features.not_run > 0 {
+            cprint!(bold White, " (");
+            cprint!(bold Yellow, "{} not run", result.features.not_run);
+            cprint!(bold White, ")");
+        }
+        println!();
 
         self.print_counter("scenarios", &result.scenarios);
         if result.rules.total > 0 {
             self.print_counter("rules", &result.rules);
         }
         self.print_counter("steps", &result.steps);
+        if result.hooks.total > 0 {
+            self.print_counter("hooks", &result.hooks);
+        }
+        self.print_snippets();
+
+        if let Some(seed) = result.seed {
+            println!("\nRandomized with seed {}", seed);
+        }
 
         let t = result.elapsed;
         println!(
@@ -453,12 +634,28 @@ impl BasicOutput {
     }
 }
 
+fn hook_name(kind: HookType) -> &'static str {
+    match kind {
+        HookType::Before => "before",
+        HookType::After => "after",
+        HookType::BeforeStep => "before_step",
+        HookType::AfterStep => "after_step",
+    }
+}
+
 impl EventHandler for BasicOutput {
     fn handle_event(&mut self, event: &CucumberEvent) {
         match event {
             CucumberEvent::Starting => {
                 cprintln!(bold termcolor::Color::Blue, "[Cucumber v{}]", env!("CARGO_PKG_VERSION"))
             }
+            CucumberEvent::Hook(kind, event) => {
+                let name = match kind {
+                    HookType::Before | HookType::BeforeStep => "before_all",
+                    HookType::After | HookType::AfterStep => "after_all",
+                };
+                self.handle_hook("", name, event);
+            }
             CucumberEvent::Finished(ref r) => self.print_finish(r),
             CucumberEvent::Feature(feature, event) => match event {
                 crate::event::FeatureEvent::Starting => {
@@ -475,6 +672,9 @@ impl EventHandler for BasicOutput {
                     }
                     self.handle_scenario(feature, None, scenario, event)
                 }
+                crate::event::FeatureEvent::Hook(kind, event) => {
+                    self.handle_hook("", hook_name(*kind), event)
+                }
                 crate::event::FeatureEvent::Rule(rule, event) => {
                     self.handle_rule(feature, rule, event)
                 }
@@ -483,6 +683,8 @@ impl EventHandler for BasicOutput {
                         println!();
                     }
                 }
+                // Counted in the summary.
+                crate::event::FeatureEvent::NotRun => {}
             },
         }
     }
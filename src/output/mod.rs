@@ -7,5 +7,7 @@
 // except according to those terms.
 
 mod default;
+mod snippet;
 
 pub use default::BasicOutput;
+pub use snippet::Snippet;
@@ -0,0 +1,148 @@
+// Copyright (c) 2018-2021  Brendan Molloy <brendan@bbqsrc.net>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use gherkin::{Step, StepType};
+use regex::Regex;
+
+/// A step definition to paste in for an undefined step.
+///
+/// Numbers and quoted strings in the step become regex captures, typed
+/// `i64`, `f64` and `String` respectively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub ty: StepType,
+    /// The regex matching the step, or `None` if it has nothing to capture
+    /// and is matched literally.
+    pub regex: Option<String>,
+    /// The step text, for steps matched literally.
+    pub text: String,
+    /// The name and type of each capture, in order.
+    pub captures: Vec<(String, &'static str)>,
+}
+
+impl Snippet {
+    pub fn new(step: &Step) -> Snippet {
+        let parameters = Regex::new(r#""[^"]*"|-?\b\d+(\.\d+)?\b"#).unwrap();
+
+        let mut regex = String::from("^");
+        let mut captures: Vec<(String, &'static str)> = vec![];
+        let mut last = 0;
+        for m in parameters.find_iter(&step.value) {
+            regex.push_str(&regex::escape(&step.value[last..m.start()]));
+            last = m.end();
+
+            let (name, ty, pattern) = if m.as_str().starts_with('"') {
+                ("string", "String", r#""([^"]*)""#)
+            } else if m.as_str().contains('.') {
+                ("float", "f64", r"(-?\d+\.\d+)")
+            } else {
+                ("int", "i64", r"(-?\d+)")
+            };
+            regex.push_str(pattern);
+
+            let count = captures.iter().filter(|(_, t)| *t == ty).count();
+            let name = match count {
+                0 => name.to_string(),
+                n => format!("{}{}", name, n + 1),
+            };
+            captures.push((name, ty));
+        }
+        regex.push_str(&regex::escape(&step.value[last..]));
+        regex.push('$');
+
+        Snippet {
+            ty: step.ty,
+            regex: if captures.is_empty() {
+                None
+            } else {
+                Some(regex)
+            },
+            text: step.value.clone(),
+            captures,
+        }
+    }
+
+    fn keyword(&self) -> &'static str {
+        match self.ty {
+            StepType::Given => "given",
+            StepType::When => "when",
+            StepType::Then => "then",
+        }
+    }
+
+    /// The name of the step function, made of the words of the step.
+    fn function_name(&self) -> String {
+        let text = Regex::new(r#""[^"]*"|\d+"#)
+            .unwrap()
+            .replace_all(&self.text, " ");
+        let words = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>();
+
+        match words.first() {
+            Some(w) if !w.starts_with(|c: char| c.is_numeric()) => words.join("_"),
+            _ => format!("step_{}", words.join("_")),
+        }
+    }
+
+    /// The step definition in the `Steps` builder style.
+    pub fn builder(&self) -> String {
+        match self.regex {
+            Some(ref regex) => {
+                let parse = self
+                    .captures
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (name, ty))| {
+                        format!(
+                            "    let {}: {} = ctx.matches[{}].parse().unwrap();\n",
+                            name,
+                            ty,
+                            i + 1
+                        )
+                    })
+                    .collect::<String>();
+                format!(
+                    "steps.{}_regex(r#\"{}\"#, |world, ctx| {{\n{}    todo!()\n}});",
+                    self.keyword(),
+                    regex,
+                    parse,
+                )
+            }
+            None => format!(
+                "steps.{}({:?}, |world, ctx| {{\n    todo!()\n}});",
+                self.keyword(),
+                self.text,
+            ),
+        }
+    }
+
+    /// The step definition in the attribute style of the `macros` feature,
+    /// for a world of type `world`.
+    pub fn attribute(&self, world: &str) -> String {
+        let pattern = match self.regex {
+            Some(ref regex) => format!("regex = r#\"{}\"#", regex),
+            None => format!("{:?}", self.text),
+        };
+        let args = self
+            .captures
+            .iter()
+            .map(|(name, ty)| format!(", {}: {}", name, ty))
+            .collect::<String>();
+        format!(
+            "#[{}({})]\nasync fn {}(world: &mut {}{}) {{\n    todo!()\n}}",
+            self.keyword(),
+            pattern,
+            self.function_name(),
+            world,
+            args,
+        )
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright (c) 2018-2021  Brendan Molloy <brendan@bbqsrc.net>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rerun files list the scenarios which failed as `path:line`, one per
+//! line, where `line` is the line of the scenario or of its example row.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::event::{CucumberEvent, FeatureEvent, RuleEvent, ScenarioEvent};
+
+/// The scenarios to run, as canonical feature file paths and lines.
+pub(crate) type Locations = HashSet<(PathBuf, usize)>;
+
+/// Collects the location of every failed scenario from the events of a run.
+#[derive(Default)]
+pub(crate) struct RerunCollector {
+    /// The line of the scenario or example row currently being reported.
+    line: usize,
+    failures: Vec<String>,
+}
+
+impl RerunCollector {
+    pub fn handle_event(&mut self, event: &CucumberEvent) {
+        let (feature, scenario, event) = match event {
+            CucumberEvent::Feature(feature, FeatureEvent::Scenario(scenario, event))
+            | CucumberEvent::Feature(
+                feature,
+                FeatureEvent::Rule(_, RuleEvent::Scenario(scenario, event)),
+            ) => (feature, scenario, event),
+            _ => return,
+        };
+
+        match event {
+            ScenarioEvent::Starting(example) => {
+                self.line = example.line.unwrap_or(scenario.position.line);
+            }
+            ScenarioEvent::Failed(_) => {
+                let path = feature
+                    .path
+                    .as_ref()
+                    .map(|path| {
+                        std::env::current_dir()
+                            .ok()
+                            .and_then(|dir| pathdiff::diff_paths(path, dir))
+                            .unwrap_or_else(|| path.clone())
+                    })
+                    .unwrap_or_default();
+                self.failures
+                    .push(format!("{}:{}", path.to_string_lossy(), self.line));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let mut content = self.failures.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        std::fs::write(path, content)
+    }
+}
+
+/// Reads the locations listed in the rerun file at `path`.
+pub(crate) fn read(path: &Path) -> std::io::Result<Locations> {
+    let invalid = |line: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("expected 'path:line', found '{}'", line),
+        )
+    };
+
+    std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (file, number) = line.rsplit_once(':').ok_or_else(|| invalid(line))?;
+            let number = number.parse().map_err(|_| invalid(line))?;
+            Ok((Path::new(file).canonicalize()?, number))
+        })
+        .collect()
+}
@@ -7,26 +7,37 @@
 // except according to those terms.
 
 use std::any::Any;
+use std::cell::{Cell, RefCell};
 use std::panic;
 use std::pin::Pin;
 use std::rc::Rc;
-use std::sync::{Arc, TryLockError};
+use std::task::Poll;
 
 use async_stream::stream;
-use futures::{Future, FutureExt, Stream, StreamExt, TryFutureExt};
+use futures::{future::Either, Future, FutureExt, Stream, StreamExt, TryFutureExt};
+use gherkin::tagexpr::TagOperation;
 use regex::Regex;
 
 use crate::{
-    collection::StepsCollection,
+    collection::{Resolution, StepsCollection},
+    concurrency::{ordered_merge, Semaphore},
     criteria::Criteria,
     cucumber::{Context, LifecycleContext, StepContext},
+    tags,
 };
-use crate::{cucumber::LifecycleFn, event::*};
-use crate::{TestError, World, TEST_SKIPPED};
+use crate::{
+    cucumber::{AfterAllFn, BeforeAllFn, LifecycleFn, Outcome, StepHookFn, WorldLifecycleFn},
+    event::*,
+};
+use crate::{examples::RowLines, rerun::Locations, Shard, TestError, World, TEST_SKIPPED};
 
 use super::ExampleValues;
 use std::time::{Duration, Instant};
 
+/// How long the after hooks of a scenario which ran out of time are given
+/// to run.
+const AFTER_HOOK_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 pub(crate) type TestFuture<W> = Pin<Box<dyn Future<Output = Result<W, TestError>>>>;
 
 impl<W> From<fn(W, StepContext) -> W> for TestFunction<W> {
@@ -85,6 +96,36 @@ fn coerce_error(err: &(dyn Any + Send + 'static)) -> String {
     }
 }
 
+/// Formats an error followed by each of its sources.
+fn error_chain(err: &dyn std::error::Error) -> Vec<String> {
+    let mut chain = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(err) = source {
+        chain.push(err.to_string());
+        source = err.source();
+    }
+    chain
+}
+
+/// Runs `future` to completion unless `deadline` passes first, in which case
+/// the failure kind attached to the deadline is returned.
+async fn until<F: Future>(
+    deadline: Option<(Instant, FailureKind)>,
+    future: F,
+) -> Result<F::Output, FailureKind> {
+    match deadline {
+        Some((at, kind)) => {
+            let timeout = futures_timer::Delay::new(at.saturating_duration_since(Instant::now()));
+            futures::pin_mut!(future);
+            match futures::future::select(future, timeout).await {
+                Either::Left((output, _)) => Ok(output),
+                Either::Right(_) => Err(kind),
+            }
+        }
+        None => Ok(future.await),
+    }
+}
+
 /// Stats for various event results
 #[derive(Debug, Default, Clone)]
 pub struct Stats {
@@ -98,6 +139,10 @@ pub struct Stats {
     pub failed: u32,
     /// events that timed out
     pub timed_out: u32,
+    /// events that were never started, e.g. because fail-fast stopped the run
+    pub not_run: u32,
+    /// events that only passed after being retried
+    pub flaky: u32,
 }
 
 impl Stats {
@@ -122,12 +167,17 @@ pub struct RunResult {
     pub scenarios: Stats,
     /// Stats of scenarios of this run
     pub steps: Stats,
+    /// Stats of hooks of this run
+    pub hooks: Stats,
+    /// The seed features and scenarios were shuffled with, if they were run
+    /// in random order
+    pub seed: Option<u64>,
 }
 
 impl RunResult {
     /// Indicates this has failing states (aka failed or timed_out)
     pub fn failed(&self) -> bool {
-        self.features.failed() || self.scenarios.failed()
+        self.features.failed() || self.scenarios.failed() || self.hooks.failed()
     }
 }
 
@@ -138,6 +188,9 @@ struct StatsCollector {
     rules: Stats,
     scenarios: Stats,
     steps: Stats,
+    hooks: Stats,
+    /// Whether the scenario currently being reported has been retried
+    retried: bool,
 }
 
 impl StatsCollector {
@@ -148,6 +201,8 @@ impl StatsCollector {
             rules: Default::default(),
             scenarios: Default::default(),
             steps: Default::default(),
+            hooks: Default::default(),
+            retried: false,
         }
     }
 
@@ -157,18 +212,19 @@ impl StatsCollector {
                 self.rules.total += 1;
             }
             RuleEvent::Scenario(_, ref event) => self.handle_scenario_event(event),
+            RuleEvent::Hook(_, ref event) => self.handle_hook_event(event),
             RuleEvent::Skipped => {
                 self.rules.skipped += 1;
             }
             RuleEvent::Passed => {
                 self.rules.passed += 1;
             }
-            RuleEvent::Failed(FailureKind::Panic) => {
-                self.rules.failed += 1;
-            }
-            RuleEvent::Failed(FailureKind::TimedOut) => {
+            RuleEvent::Failed(kind) if kind.is_timeout() => {
                 self.rules.timed_out += 1;
             }
+            RuleEvent::Failed(_) => {
+                self.rules.failed += 1;
+            }
         }
     }
 
@@ -176,31 +232,42 @@ impl StatsCollector {
         match event {
             ScenarioEvent::Starting(_) => {
                 self.scenarios.total += 1;
+                self.retried = false;
             }
             ScenarioEvent::Background(_, ref event) => self.handle_step_event(event),
             ScenarioEvent::Step(_, ref event) => self.handle_step_event(event),
             ScenarioEvent::Skipped => {
                 self.scenarios.skipped += 1;
             }
+            ScenarioEvent::Retrying(_) => {
+                self.retried = true;
+            }
+            ScenarioEvent::Hook(_, ref event) => self.handle_hook_event(event),
+            ScenarioEvent::TeardownFailed(_) => {
+                // the scenario outcome that follows is counted instead
+            }
+            ScenarioEvent::Passed if self.retried => {
+                self.scenarios.flaky += 1;
+            }
             ScenarioEvent::Passed => {
                 self.scenarios.passed += 1;
             }
-            ScenarioEvent::Failed(FailureKind::Panic) => {
-                self.scenarios.failed += 1;
-            }
-            ScenarioEvent::Failed(FailureKind::TimedOut) => {
+            ScenarioEvent::Failed(kind) if kind.is_timeout() => {
                 self.scenarios.timed_out += 1;
             }
+            ScenarioEvent::Failed(_) => {
+                self.scenarios.failed += 1;
+            }
         }
     }
 
     fn handle_step_event(&mut self, event: &StepEvent) {
-        self.steps.total += 1;
         match event {
             StepEvent::Starting => {
                 // we don't have to count this
+                return;
             }
-            StepEvent::Unimplemented => {
+            StepEvent::Unimplemented | StepEvent::Ambiguous(_) => {
                 self.steps.skipped += 1;
             }
             StepEvent::Skipped => {
@@ -216,6 +283,7 @@ impl StatsCollector {
                 self.steps.timed_out += 1;
             }
         }
+        self.steps.total += 1;
     }
 
     fn handle_feature_event(&mut self, event: &FeatureEvent) {
@@ -225,7 +293,25 @@ impl StatsCollector {
             }
             FeatureEvent::Scenario(_, ref event) => self.handle_scenario_event(event),
             FeatureEvent::Rule(_, ref event) => self.handle_rule_event(event),
-            _ => {}
+            FeatureEvent::Hook(_, ref event) => self.handle_hook_event(event),
+            FeatureEvent::NotRun => {
+                self.features.not_run += 1;
+            }
+            FeatureEvent::Finished => {}
+        }
+    }
+
+    fn handle_hook_event(&mut self, event: &HookEvent) {
+        match event {
+            HookEvent::Starting => {
+                self.hooks.total += 1;
+            }
+            HookEvent::Passed => {
+                self.hooks.passed += 1;
+            }
+            HookEvent::Failed(_) => {
+                self.hooks.failed += 1;
+            }
         }
     }
 
@@ -236,6 +322,8 @@ impl StatsCollector {
             rules,
             scenarios,
             steps,
+            hooks,
+            ..
         } = self;
 
         RunResult {
@@ -245,19 +333,201 @@ impl StatsCollector {
             rules,
             scenarios,
             steps,
+            hooks,
+            seed: None,
+        }
+    }
+}
+
+/// Works out the `Outcome` of a scenario, rule or feature from the events of
+/// its scenarios.
+#[derive(Default)]
+struct OutcomeCollector {
+    outcome: Option<Outcome>,
+    /// Where the scenario being reported first failed.
+    failed_at: Option<(Option<Rc<gherkin::Step>>, Option<PanicInfo>)>,
+}
+
+impl OutcomeCollector {
+    fn handle_scenario_event(&mut self, event: &ScenarioEvent) {
+        match event {
+            ScenarioEvent::Starting(_) | ScenarioEvent::Retrying(_) => {
+                self.failed_at = None;
+            }
+            ScenarioEvent::Background(step, StepEvent::Failed(kind))
+            | ScenarioEvent::Step(step, StepEvent::Failed(kind))
+                if self.failed_at.is_none() =>
+            {
+                let panic_info = match kind {
+                    StepFailureKind::Panic(_, pi) => Some(pi.clone()),
+                    StepFailureKind::TimedOut => None,
+                };
+                self.failed_at = Some((Some(Rc::clone(step)), panic_info));
+            }
+            ScenarioEvent::Background(step, StepEvent::Ambiguous(_))
+            | ScenarioEvent::Step(step, StepEvent::Ambiguous(_))
+                if self.failed_at.is_none() =>
+            {
+                self.failed_at = Some((Some(Rc::clone(step)), None));
+            }
+            ScenarioEvent::Hook(_, HookEvent::Failed(kind)) if self.failed_at.is_none() => {
+                let panic_info = match kind {
+                    HookFailureKind::Panic(_, pi) => Some(pi.clone()),
+                    _ => None,
+                };
+                self.failed_at = Some((None, panic_info));
+            }
+            ScenarioEvent::Failed(kind) => self.fail(kind.clone()),
+            ScenarioEvent::Passed if self.outcome.is_none() => {
+                self.outcome = Some(Outcome::Passed);
+            }
+            ScenarioEvent::Skipped if !self.is_failed() => {
+                self.outcome = Some(Outcome::Skipped);
+            }
+            _ => {}
+        }
+    }
+
+    fn is_failed(&self) -> bool {
+        matches!(self.outcome, Some(Outcome::Failed { .. }))
+    }
+
+    /// Fails the outcome, unless something failed before.
+    fn fail(&mut self, kind: FailureKind) {
+        if !self.is_failed() {
+            let (step, panic_info) = self.failed_at.take().unwrap_or_default();
+            self.outcome = Some(Outcome::Failed {
+                kind,
+                step,
+                panic_info,
+            });
+        }
+    }
+
+    fn outcome(&self) -> Outcome {
+        self.outcome.clone().unwrap_or(Outcome::Skipped)
+    }
+}
+
+/// Execution settings handed from `Cucumber` to the `Runner`.
+pub(crate) struct RunnerOptions {
+    pub step_timeout: Option<Duration>,
+    pub enable_capture: bool,
+    pub scenario_filter: Option<Regex>,
+    pub tag_filter: Option<TagOperation>,
+    pub max_concurrent_scenarios: usize,
+    pub fail_fast: bool,
+    pub retries: u32,
+    pub scenario_timeout: Option<Duration>,
+    pub run_timeout: Option<Duration>,
+    pub dry_run: bool,
+    pub fail_on_ambiguous_steps: bool,
+    pub seed: Option<u64>,
+    pub shard: Option<Shard>,
+    pub locations: Option<Locations>,
+    pub row_lines: RowLines,
+}
+
+/// Lifecycle hooks handed from `Cucumber` to the `Runner`.
+pub(crate) struct Hooks<W> {
+    pub before: Vec<(Criteria, LifecycleFn)>,
+    pub after: Vec<(Criteria, LifecycleFn)>,
+    pub before_world: Vec<(Criteria, WorldLifecycleFn<W>)>,
+    pub after_world: Vec<(Criteria, WorldLifecycleFn<W>)>,
+    pub before_step: Vec<(Criteria, StepHookFn<W>)>,
+    pub after_step: Vec<(Criteria, StepHookFn<W>)>,
+    pub before_all: Vec<BeforeAllFn>,
+    pub after_all: Vec<AfterAllFn>,
+}
+
+impl<W> Default for Hooks<W> {
+    fn default() -> Self {
+        Hooks {
+            before: vec![],
+            after: vec![],
+            before_world: vec![],
+            after_world: vec![],
+            before_step: vec![],
+            after_step: vec![],
+            before_all: vec![],
+            after_all: vec![],
         }
     }
 }
 
 pub(crate) struct Runner<W: World> {
-    context: Rc<Context>,
+    /// Taken out for the `before_all` hooks, before anything else holds on
+    /// to it.
+    context: RefCell<Rc<Context>>,
     functions: StepsCollection<W>,
     features: Rc<Vec<gherkin::Feature>>,
     step_timeout: Option<Duration>,
     enable_capture: bool,
     scenario_filter: Option<Regex>,
-    before: Vec<(Criteria, LifecycleFn)>,
-    after: Vec<(Criteria, LifecycleFn)>,
+    tag_filter: Option<TagOperation>,
+    max_concurrent_scenarios: usize,
+    permits: Rc<Semaphore>,
+    fail_fast: bool,
+    stopped: Cell<bool>,
+    not_run: Cell<u32>,
+    retries: u32,
+    scenario_timeout: Option<Duration>,
+    run_timeout: Option<Duration>,
+    run_deadline: Cell<Option<Instant>>,
+    dry_run: bool,
+    fail_on_ambiguous_steps: bool,
+    seed: Option<u64>,
+    shard: Option<Shard>,
+    /// If given, only the scenarios and example rows at these locations are
+    /// run.
+    locations: Option<Locations>,
+    /// The lines of the example rows, found when the features were loaded.
+    row_lines: RowLines,
+    hooks: Hooks<W>,
+}
+
+thread_local! {
+    /// Where the panic hook records the panic of the step currently being polled.
+    static PANIC_INFO: RefCell<Option<Rc<RefCell<Option<PanicInfo>>>>> = const { RefCell::new(None) };
+}
+
+fn record_panic(location: Option<&panic::Location<'_>>, payload: &(dyn Any + Send + 'static)) {
+    let _ = PANIC_INFO.try_with(|slot| {
+        if let Ok(slot) = slot.try_borrow() {
+            if let Some(slot) = slot.as_ref() {
+                *slot.borrow_mut() = Some(PanicInfo {
+                    location: location
+                        .map(|l| Location {
+                            file: l.file().to_string(),
+                            line: l.line(),
+                            column: l.column(),
+                        })
+                        .unwrap_or_else(Location::unknown),
+                    payload: coerce_error(payload),
+                });
+            }
+        }
+    });
+}
+
+/// Makes the given slot the target of the panic hook whenever the inner
+/// future is polled, so that concurrently running steps never see each
+/// other's panics.
+struct TrapPanicInfo<F> {
+    inner: F,
+    slot: Rc<RefCell<Option<PanicInfo>>>,
+}
+
+impl<F: Future + Unpin> Future for TrapPanicInfo<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<F::Output> {
+        let slot = Rc::clone(&self.slot);
+        let previous = PANIC_INFO.with(|current| current.replace(Some(slot)));
+        let result = Pin::new(&mut self.inner).poll(cx);
+        PANIC_INFO.with(|current| current.replace(previous));
+        result
+    }
 }
 
 impl<W: World> Runner<W> {
@@ -266,98 +536,164 @@ impl<W: World> Runner<W> {
         context: Rc<Context>,
         functions: StepsCollection<W>,
         features: Rc<Vec<gherkin::Feature>>,
-        step_timeout: Option<Duration>,
-        enable_capture: bool,
-        scenario_filter: Option<Regex>,
-        before: Vec<(Criteria, LifecycleFn)>,
-        after: Vec<(Criteria, LifecycleFn)>,
+        options: RunnerOptions,
+        hooks: Hooks<W>,
     ) -> Rc<Runner<W>> {
+        let max_concurrent_scenarios = options.max_concurrent_scenarios.max(1);
+        // Nothing but step resolution happens in a dry run.
+        let hooks = if options.dry_run {
+            Hooks::default()
+        } else {
+            hooks
+        };
+
         Rc::new(Runner {
-            context,
+            context: RefCell::new(context),
             functions,
             features,
-            step_timeout,
-            enable_capture,
-            scenario_filter,
-            before,
-            after,
+            step_timeout: options.step_timeout,
+            enable_capture: options.enable_capture,
+            scenario_filter: options.scenario_filter,
+            tag_filter: options.tag_filter,
+            max_concurrent_scenarios,
+            permits: Semaphore::new(max_concurrent_scenarios),
+            fail_fast: options.fail_fast,
+            stopped: Cell::new(false),
+            not_run: Cell::new(0),
+            retries: options.retries,
+            scenario_timeout: options.scenario_timeout,
+            run_timeout: options.run_timeout,
+            run_deadline: Cell::new(None),
+            dry_run: options.dry_run,
+            fail_on_ambiguous_steps: options.fail_on_ambiguous_steps,
+            seed: options.seed,
+            shard: options.shard,
+            locations: options.locations,
+            row_lines: options.row_lines,
+            hooks,
         })
     }
 
-    async fn run_step(self: Rc<Self>, step: Rc<gherkin::Step>, world: W) -> TestEvent<W> {
-        use std::io::prelude::*;
+    fn context(&self) -> Rc<Context> {
+        Rc::clone(&self.context.borrow())
+    }
 
-        let func = match self.functions.resolve(&step) {
-            Some(v) => v,
-            None => return TestEvent::Unimplemented,
+    /// Whether a run of the scenario is listed in the locations to run, by
+    /// the line of either the scenario or its example row.
+    fn is_location_match(
+        &self,
+        feature: &gherkin::Feature,
+        scenario: &gherkin::Scenario,
+        example: &ExampleValues,
+    ) -> bool {
+        let locations = match self.locations {
+            Some(ref locations) => locations,
+            None => return true,
         };
-
-        let mut maybe_capture_handles = if self.enable_capture {
-            Some((shh::stdout().unwrap(), shh::stderr().unwrap()))
-        } else {
-            None
+        let path = match feature.path {
+            Some(ref path) => path.clone(),
+            None => return false,
         };
 
-        // This ugly mess here catches the panics from async calls.
-        let panic_info = Arc::new(std::sync::Mutex::new(None));
-        let panic_info0 = Arc::clone(&panic_info);
-        let step_timeout0 = self.step_timeout;
-        panic::set_hook(Box::new(move |pi| {
-            let panic_info = Some(PanicInfo {
-                location: pi
-                    .location()
-                    .map(|l| Location {
-                        file: l.file().to_string(),
-                        line: l.line(),
-                        column: l.column(),
-                    })
-                    .unwrap_or_else(Location::unknown),
-                payload: coerce_error(pi.payload()),
-            });
-            if let Some(step_timeout) = step_timeout0 {
-                let start_point = Instant::now();
-                loop {
-                    match panic_info0.try_lock() {
-                        Ok(mut guard) => {
-                            *guard = panic_info;
-                            return;
-                        }
-                        Err(TryLockError::WouldBlock) => {
-                            if start_point.elapsed() < step_timeout {
-                                continue;
-                            } else {
-                                return;
-                            }
-                        }
-                        Err(TryLockError::Poisoned(_)) => {
-                            return;
-                        }
-                    }
-                }
-            } else {
-                *panic_info0.lock().unwrap() = panic_info;
+        std::iter::once(scenario.position.line)
+            .chain(example.line)
+            .any(|line| locations.contains(&(path.clone(), line)))
+    }
+
+    /// Whether a run of the scenario passes the tag filter, taking the tags
+    /// of its feature, rule and `Examples` block into account.
+    fn is_tag_match(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        example: &ExampleValues,
+    ) -> bool {
+        match self.tag_filter {
+            Some(ref op) => {
+                let tags = tags::inherited(feature, rule, Some(scenario))
+                    .chain(example.tags.iter())
+                    .collect::<Vec<_>>();
+                tags::eval(op, &tags)
             }
-        }));
+            None => true,
+        }
+    }
 
-        let context = Rc::clone(&self.context);
+    /// How many times a failing scenario may be retried, either from its
+    /// `@retry-N` tag or from the runner's default.
+    fn retries_for(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) -> u32 {
+        tags::value(tags::inherited(feature, rule, Some(scenario)), "retry")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(self.retries)
+    }
 
-        let step_future = match func {
-            TestFunction::BasicAsync(f) => (f)(world, StepContext::new(context, step, vec![])),
-            TestFunction::RegexAsync(f, r) => (f)(world, StepContext::new(context, step, r)),
+    /// The point in time by which the current attempt of a scenario has to
+    /// finish, either because of its own time limit or that of the whole run.
+    fn scenario_deadline(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) -> Option<(Instant, FailureKind)> {
+        let timeout = tags::value(tags::inherited(feature, rule, Some(scenario)), "timeout")
+            .and_then(tags::duration)
+            .or(self.scenario_timeout);
+        let scenario_deadline =
+            timeout.map(|t| (Instant::now() + t, FailureKind::ScenarioTimedOut));
+
+        match (scenario_deadline, self.run_deadline()) {
+            (Some(a), Some(b)) if b.0 < a.0 => Some(b),
+            (Some(a), _) => Some(a),
+            (None, b) => b,
+        }
+    }
 
-            TestFunction::BasicSync(test_fn) => std::panic::AssertUnwindSafe(async move {
-                (test_fn)(world, StepContext::new(context, step, vec![]))
-            })
-            .catch_unwind()
-            .map_err(TestError::PanicError)
-            .boxed_local(),
+    fn run_deadline(&self) -> Option<(Instant, FailureKind)> {
+        self.run_deadline
+            .get()
+            .map(|at| (at, FailureKind::RunTimedOut))
+    }
 
-            TestFunction::RegexSync(test_fn, matches) => std::panic::AssertUnwindSafe(async move {
-                (test_fn)(world, StepContext::new(context, step, matches))
-            })
-            .catch_unwind()
-            .map_err(TestError::PanicError)
-            .boxed_local(),
+    /// Whether fail-fast or the run timeout has stopped the run from starting
+    /// anything new.
+    fn is_stopped(&self) -> bool {
+        self.stopped.get()
+            || self
+                .run_deadline
+                .get()
+                .map(|at| Instant::now() >= at)
+                .unwrap_or(false)
+    }
+
+    /// Polls `future` with its stdout and stderr captured, its panic
+    /// recorded and within the step timeout.
+    async fn trap<'a, T>(
+        &self,
+        future: Pin<Box<dyn Future<Output = Result<T, TestError>> + 'a>>,
+    ) -> (Result<T, TestError>, CapturedOutput, Option<PanicInfo>) {
+        use std::io::prelude::*;
+
+        // Redirecting stdout and stderr is process-wide, so output can only
+        // be attributed to a step when steps never overlap.
+        let mut maybe_capture_handles = if self.enable_capture && self.max_concurrent_scenarios == 1
+        {
+            Some((shh::stdout().unwrap(), shh::stderr().unwrap()))
+        } else {
+            None
+        };
+
+        panic::set_hook(Box::new(|pi| record_panic(pi.location(), pi.payload())));
+        let panic_info = Rc::new(RefCell::new(None));
+
+        let future = TrapPanicInfo {
+            inner: future,
+            slot: Rc::clone(&panic_info),
         };
 
         let result = if let Some(step_timeout) = self.step_timeout {
@@ -365,12 +701,12 @@ impl<W: World> Runner<W> {
                 futures_timer::Delay::new(step_timeout).await;
                 Err(TestError::TimedOut)
             });
-            futures::future::select(timeout, step_future)
+            futures::future::select(timeout, future)
                 .await
                 .factor_first()
                 .0
         } else {
-            step_future.await
+            future.await
         };
 
         let mut out = String::new();
@@ -388,7 +724,39 @@ impl<W: World> Runner<W> {
             });
         }
 
-        let output = CapturedOutput { out, err };
+        let panic_info = panic_info.borrow_mut().take();
+        (result, CapturedOutput { out, err }, panic_info)
+    }
+
+    async fn run_step(self: Rc<Self>, step: Rc<gherkin::Step>, world: W) -> TestEvent<W> {
+        let func = match self.functions.resolve(&step) {
+            Resolution::Found(v) => v,
+            Resolution::Ambiguous(patterns) => return TestEvent::Ambiguous(patterns),
+            Resolution::Undefined => return TestEvent::Unimplemented,
+        };
+
+        let context = self.context();
+
+        let step_future = match func {
+            TestFunction::BasicAsync(f) => (f)(world, StepContext::new(context, step, vec![])),
+            TestFunction::RegexAsync(f, r) => (f)(world, StepContext::new(context, step, r)),
+
+            TestFunction::BasicSync(test_fn) => std::panic::AssertUnwindSafe(async move {
+                (test_fn)(world, StepContext::new(context, step, vec![]))
+            })
+            .catch_unwind()
+            .map_err(TestError::PanicError)
+            .boxed_local(),
+
+            TestFunction::RegexSync(test_fn, matches) => std::panic::AssertUnwindSafe(async move {
+                (test_fn)(world, StepContext::new(context, step, matches))
+            })
+            .catch_unwind()
+            .map_err(TestError::PanicError)
+            .boxed_local(),
+        };
+
+        let (result, output, panic_info) = self.trap(step_future).await;
         match result {
             Ok(w) => TestEvent::Success(w, output),
             Err(TestError::TimedOut) => TestEvent::Failure(StepFailureKind::TimedOut),
@@ -398,53 +766,51 @@ impl<W: World> Runner<W> {
                     return TestEvent::Skipped;
                 }
 
-                let pi = if let Some(step_timeout) = self.step_timeout {
-                    let start_point = Instant::now();
-                    loop {
-                        match panic_info.try_lock() {
-                            Ok(mut guard) => {
-                                break guard.take().unwrap_or_else(PanicInfo::unknown);
-                            }
-                            Err(TryLockError::WouldBlock) => {
-                                if start_point.elapsed() < step_timeout {
-                                    futures_timer::Delay::new(Duration::from_micros(10)).await;
-                                    continue;
-                                } else {
-                                    break PanicInfo::unknown();
-                                }
-                            }
-                            Err(TryLockError::Poisoned(_)) => break PanicInfo::unknown(),
-                        }
-                    }
-                } else {
-                    let mut guard = panic_info.lock().unwrap();
-                    guard.take().unwrap_or_else(PanicInfo::unknown)
-                };
+                let pi = panic_info.unwrap_or_else(PanicInfo::unknown);
                 TestEvent::Failure(StepFailureKind::Panic(output, pi))
             }
         }
     }
 
-    fn run_feature(self: Rc<Self>, feature: Rc<gherkin::Feature>) -> FeatureStream {
-        Box::pin(stream! {
-            yield FeatureEvent::Starting;
-
-            let context = LifecycleContext {
-                context: self.context.clone(),
-                feature: Rc::clone(&feature),
-                rule: None,
-                scenario: None,
-            };
+    /// Runs a hook the way steps are run, returning the event reporting its
+    /// outcome and, if it failed, how the surrounding scenario failed.
+    async fn run_hook<'a>(
+        &self,
+        hook: Pin<Box<dyn Future<Output = Result<(), Vec<String>>> + 'a>>,
+        deadline: Option<(Instant, FailureKind)>,
+    ) -> (HookEvent, Result<(), FailureKind>) {
+        let hook = std::panic::AssertUnwindSafe(hook)
+            .catch_unwind()
+            .map_err(TestError::PanicError)
+            .boxed_local();
 
-            for (criteria, handler) in self.before.iter() {
-                if !criteria.context().is_feature() {
-                    continue;
-                }
+        let (result, output, panic_info) = match until(deadline, self.trap(hook)).await {
+            Ok(v) => v,
+            Err(kind) => return (HookEvent::Failed(HookFailureKind::TimedOut), Err(kind)),
+        };
 
-                if criteria.eval(&*feature, None, None) {
-                    (handler)(context.clone()).await;
-                }
+        let failure = match result {
+            Ok(Ok(())) => return (HookEvent::Passed, Ok(())),
+            Ok(Err(chain)) => HookFailureKind::Error(chain),
+            Err(TestError::TimedOut) => {
+                return (
+                    HookEvent::Failed(HookFailureKind::TimedOut),
+                    Err(FailureKind::TimedOut),
+                )
             }
+            Err(TestError::PanicError(_)) => {
+                HookFailureKind::Panic(output, panic_info.unwrap_or_else(PanicInfo::unknown))
+            }
+        };
+        (HookEvent::Failed(failure), Err(FailureKind::Hook))
+    }
+
+    fn run_feature(self: Rc<Self>, feature: Rc<gherkin::Feature>) -> FeatureStream {
+        Box::pin(stream! {
+            let mut streams: Vec<FeatureStream> = vec![];
+            // Set when a `before` hook of the feature fails, failing all of
+            // its scenarios without running them.
+            let hook_failed = Rc::new(Cell::new(false));
 
             for scenario in feature.scenarios.iter() {
                 // If regex filter fails, skip the scenario
@@ -454,16 +820,8 @@ impl<W: World> Runner<W> {
                     }
                 }
 
-                let examples = ExampleValues::from_examples(&scenario.examples);
-                for example_values in examples {
-                    let this = Rc::clone(&self);
-                    let scenario = Rc::new(scenario.clone());
-
-                    let mut stream = this.run_scenario(Rc::clone(&scenario), None, Rc::clone(&feature), example_values);
-
-                    while let Some(event) = stream.next().await {
-                        yield FeatureEvent::Scenario(Rc::clone(&scenario), event);
-                    }
+                for (scenario, stream) in self.run_examples(scenario, None, &feature, &hook_failed) {
+                    streams.push(Box::pin(stream.map(move |event| FeatureEvent::Scenario(Rc::clone(&scenario), event))));
                 }
             }
 
@@ -471,20 +829,79 @@ impl<W: World> Runner<W> {
                 let this = Rc::clone(&self);
                 let rule = Rc::new(rule.clone());
 
-                let mut stream = this.run_rule(Rc::clone(&rule), Rc::clone(&feature));
+                let stream = this.run_rule(Rc::clone(&rule), Rc::clone(&feature), Rc::clone(&hook_failed));
+                streams.push(Box::pin(stream.map(move |event| FeatureEvent::Rule(Rc::clone(&rule), event))));
+            }
+
+            let mut stream = ordered_merge(futures::stream::iter(streams), self.max_concurrent_scenarios);
+
+            if self.is_stopped() {
+                // Let every scenario account for itself as not run.
+                while stream.next().await.is_some() {}
+                yield FeatureEvent::NotRun;
+                return;
+            }
+
+            yield FeatureEvent::Starting;
 
-                while let Some(event) = stream.next().await {
-                    yield FeatureEvent::Rule(Rc::clone(&rule), event);
+            let context = LifecycleContext {
+                context: self.context(),
+                feature: Rc::clone(&feature),
+                rule: None,
+                scenario: None,
+                outcome: None,
+            };
+
+            for (criteria, handler) in self.hooks.before.iter() {
+                if !criteria.context().is_feature() {
+                    continue;
+                }
+
+                if criteria.eval(&feature, None, None) {
+                    // Holding a permit keeps the hook from running alongside
+                    // a `@serial` scenario.
+                    let permit = self.permits.acquire(1).await;
+                    yield FeatureEvent::Hook(HookType::Before, HookEvent::Starting);
+                    let hook = Box::pin((handler)(context.clone()).map(Ok));
+                    let (event, result) = self.run_hook(hook, self.run_deadline()).await;
+                    drop(permit);
+                    yield FeatureEvent::Hook(HookType::Before, event);
+                    if result.is_err() {
+                        hook_failed.set(true);
+                        break;
+                    }
+                }
+            }
+
+            let mut outcome = OutcomeCollector::default();
+            while let Some(event) = stream.next().await {
+                match event {
+                    FeatureEvent::Scenario(_, ref event)
+                    | FeatureEvent::Rule(_, RuleEvent::Scenario(_, ref event)) => {
+                        outcome.handle_scenario_event(event)
+                    }
+                    _ => {}
                 }
+                yield event;
             }
 
-            for (criteria, handler) in self.after.iter() {
+            let context = LifecycleContext {
+                outcome: Some(outcome.outcome()),
+                ..context
+            };
+
+            for (criteria, handler) in self.hooks.after.iter() {
                 if !criteria.context().is_feature() {
                     continue;
                 }
 
-                if criteria.eval(&*feature, None, None) {
-                    (handler)(context.clone()).await;
+                if criteria.eval(&feature, None, None) {
+                    let permit = self.permits.acquire(1).await;
+                    yield FeatureEvent::Hook(HookType::After, HookEvent::Starting);
+                    let hook = Box::pin((handler)(context.clone()).map(Ok));
+                    let (event, _) = self.run_hook(hook, self.run_deadline()).await;
+                    drop(permit);
+                    yield FeatureEvent::Hook(HookType::After, event);
                 }
             }
 
@@ -496,54 +913,89 @@ impl<W: World> Runner<W> {
         self: Rc<Self>,
         rule: Rc<gherkin::Rule>,
         feature: Rc<gherkin::Feature>,
+        feature_hook_failed: Rc<Cell<bool>>,
     ) -> RuleStream {
         Box::pin(stream! {
+            // The feature hooks are done by the time this stream is polled.
+            let hook_failed = Rc::new(Cell::new(feature_hook_failed.get()));
+
+            let streams = rule.scenarios.iter().flat_map(|scenario| {
+                self.run_examples(scenario, Some(Rc::clone(&rule)), &feature, &hook_failed)
+            }).map(|(scenario, stream)| {
+                stream.map(move |event| (Rc::clone(&scenario), event))
+            }).collect::<Vec<_>>();
+
+            let mut stream = ordered_merge(futures::stream::iter(streams), self.max_concurrent_scenarios);
+
+            if self.is_stopped() {
+                // Nothing in this rule will start, but its scenarios are still counted.
+                while stream.next().await.is_some() {}
+                return;
+            }
+
             yield RuleEvent::Starting;
 
             let context = LifecycleContext {
-                context: self.context.clone(),
+                context: self.context(),
                 feature: Rc::clone(&feature),
                 rule: Some(Rc::clone(&rule)),
                 scenario: None,
+                outcome: None,
             };
 
-            for (criteria, handler) in self.before.iter() {
+            for (criteria, handler) in self.hooks.before.iter().filter(|_| !hook_failed.get()) {
                 if !criteria.context().is_rule() {
                     continue;
                 }
 
-                if criteria.eval(&*feature, Some(&*rule), None) {
-                    (handler)(context.clone()).await;
+                if criteria.eval(&feature, Some(&*rule), None) {
+                    let permit = self.permits.acquire(1).await;
+                    yield RuleEvent::Hook(HookType::Before, HookEvent::Starting);
+                    let hook = Box::pin((handler)(context.clone()).map(Ok));
+                    let (event, result) = self.run_hook(hook, self.run_deadline()).await;
+                    drop(permit);
+                    yield RuleEvent::Hook(HookType::Before, event);
+                    if result.is_err() {
+                        hook_failed.set(true);
+                        break;
+                    }
                 }
             }
 
             let mut return_event = None;
-
-            for scenario in rule.scenarios.iter() {
-                let this = Rc::clone(&self);
-                let scenario = Rc::new(scenario.clone());
-
-                let mut stream = this.run_scenario(Rc::clone(&scenario), Some(Rc::clone(&rule)), Rc::clone(&feature), ExampleValues::empty());
-
-                while let Some(event) = stream.next().await {
-                    match event {
-                        ScenarioEvent::Failed(FailureKind::Panic) => { return_event = Some(RuleEvent::Failed(FailureKind::Panic)); },
-                        ScenarioEvent::Failed(FailureKind::TimedOut) => { return_event = Some(RuleEvent::Failed(FailureKind::TimedOut)); },
-                        ScenarioEvent::Passed if return_event.is_none() => { return_event = Some(RuleEvent::Passed); },
-                        ScenarioEvent::Skipped if return_event == Some(RuleEvent::Passed) => { return_event = Some(RuleEvent::Skipped); }
-                        _ => {}
-                    }
-                    yield RuleEvent::Scenario(Rc::clone(&scenario), event);
+            let mut outcome = OutcomeCollector::default();
+
+            while let Some((scenario, event)) = stream.next().await {
+                outcome.handle_scenario_event(&event);
+                match event {
+                    ScenarioEvent::Failed(ref kind) => { return_event = Some(RuleEvent::Failed(kind.clone())); },
+                    ScenarioEvent::Passed if return_event.is_none() => { return_event = Some(RuleEvent::Passed); },
+                    ScenarioEvent::Skipped if return_event == Some(RuleEvent::Passed) => { return_event = Some(RuleEvent::Skipped); }
+                    _ => {}
                 }
+                yield RuleEvent::Scenario(scenario, event);
             }
 
-            for (criteria, handler) in self.after.iter() {
+            let context = LifecycleContext {
+                outcome: Some(outcome.outcome()),
+                ..context
+            };
+
+            for (criteria, handler) in self.hooks.after.iter() {
                 if !criteria.context().is_rule() {
                     continue;
                 }
 
-                if criteria.eval(&*feature, Some(&*rule), None) {
-                    (handler)(context.clone()).await;
+                if criteria.eval(&feature, Some(&*rule), None) {
+                    let permit = self.permits.acquire(1).await;
+                    yield RuleEvent::Hook(HookType::After, HookEvent::Starting);
+                    let hook = Box::pin((handler)(context.clone()).map(Ok));
+                    let (event, result) = self.run_hook(hook, self.run_deadline()).await;
+                    drop(permit);
+                    yield RuleEvent::Hook(HookType::After, event);
+                    if let Err(kind) = result {
+                        return_event = Some(RuleEvent::Failed(kind));
+                    }
                 }
             }
 
@@ -551,161 +1003,491 @@ impl<W: World> Runner<W> {
         })
     }
 
+    /// Runs `scenario` once for every row of its examples passing the tag
+    /// filter, or just once if it has none. Nothing is run if the scenario
+    /// belongs to another shard.
+    fn run_examples(
+        self: &Rc<Self>,
+        scenario: &gherkin::Scenario,
+        rule: Option<Rc<gherkin::Rule>>,
+        feature: &Rc<gherkin::Feature>,
+        hook_failed: &Rc<Cell<bool>>,
+    ) -> Vec<(Rc<gherkin::Scenario>, ScenarioStream)> {
+        if let Some(shard) = self.shard {
+            if !shard.contains(feature.path.as_deref(), scenario.position.line) {
+                return vec![];
+            }
+        }
+
+        let examples = ExampleValues::from_examples_in(
+            &scenario.examples,
+            feature.path.as_deref(),
+            &self.row_lines,
+        );
+        examples
+            .into_iter()
+            .filter(|example| self.is_tag_match(feature, rule.as_deref(), scenario, example))
+            .filter(|example| self.is_location_match(feature, scenario, example))
+            .map(|example_values| {
+                let this = Rc::clone(self);
+                let mut scenario = scenario.clone();
+                scenario.name = example_values.insert_values(&scenario.name);
+                let scenario = Rc::new(scenario);
+
+                let stream = this.run_scenario(
+                    Rc::clone(&scenario),
+                    rule.clone(),
+                    Rc::clone(feature),
+                    example_values,
+                    Rc::clone(hook_failed),
+                );
+                (scenario, stream)
+            })
+            .collect()
+    }
+
     fn run_scenario(
         self: Rc<Self>,
         scenario: Rc<gherkin::Scenario>,
         rule: Option<Rc<gherkin::Rule>>,
         feature: Rc<gherkin::Feature>,
         example: super::ExampleValues,
+        hook_failed: Rc<Cell<bool>>,
     ) -> ScenarioStream {
-        Box::pin(stream! {
-            yield ScenarioEvent::Starting(example.clone());
-
-            let context = LifecycleContext {
-                context: self.context.clone(),
-                feature: Rc::clone(&feature),
-                rule: rule.clone(),
-                scenario: Some(Rc::clone(&scenario)),
+        let this = Rc::clone(&self);
+        let stream = stream! {
+            // `@serial` scenarios wait for every running scenario to finish
+            // and hold back the ones after them until they are done.
+            let _permit = if tags::inherited(&feature, rule.as_deref(), Some(&scenario)).any(|t| t == "serial") {
+                self.permits.acquire_all().await
+            } else {
+                self.permits.acquire(1).await
             };
 
-            for (criteria, handler) in self.before.iter() {
-                if !criteria.context().is_scenario() {
-                    continue;
+            if self.is_stopped() {
+                self.not_run.set(self.not_run.get() + 1);
+                return;
+            }
+
+            yield ScenarioEvent::Starting(example.clone());
+
+            let retries = self.retries_for(&feature, rule.as_deref(), &scenario);
+            let mut attempt = 0;
+
+            // The feature background runs first, then the one of the rule.
+            let background = feature.background.iter()
+                .chain(rule.as_ref().and_then(|rule| rule.background.as_ref()))
+                .flat_map(|x| x.steps.iter())
+                .map(|step| (true, step.clone()));
+            let steps = scenario.steps.iter().map(|step| (false, example.insert_into_step(step)));
+            let steps = background.chain(steps).map(|(is_bg, step)| (is_bg, Rc::new(step))).collect::<Vec<_>>();
+
+            // A failed hook of the feature or rule leaves nothing to run, but
+            // the steps are still reported so the scenario is shown in full.
+            if hook_failed.get() {
+                for (is_bg, step) in steps.iter() {
+                    yield if *is_bg {
+                        ScenarioEvent::Background(Rc::clone(step), StepEvent::Skipped)
+                    } else {
+                        ScenarioEvent::Step(Rc::clone(step), StepEvent::Skipped)
+                    };
                 }
+                yield ScenarioEvent::Failed(FailureKind::Hook);
+                return;
+            }
 
-                if criteria.eval(&*feature, rule.as_ref().map(|x| &**x), Some(&*scenario)) {
-                    (handler)(context.clone()).await;
+            // A dry run only resolves the steps, without creating a world.
+            if self.dry_run {
+                let mut failure = None;
+                for (is_bg, step) in steps.iter() {
+                    let event = match self.functions.resolve(step) {
+                        Resolution::Found(_) => StepEvent::Skipped,
+                        Resolution::Ambiguous(patterns) => {
+                            failure.get_or_insert(FailureKind::Ambiguous);
+                            StepEvent::Ambiguous(patterns)
+                        }
+                        Resolution::Undefined => {
+                            failure.get_or_insert(FailureKind::Undefined);
+                            StepEvent::Unimplemented
+                        }
+                    };
+                    yield if *is_bg {
+                        ScenarioEvent::Background(Rc::clone(step), event)
+                    } else {
+                        ScenarioEvent::Step(Rc::clone(step), event)
+                    };
                 }
+                yield match failure {
+                    Some(kind) => ScenarioEvent::Failed(kind),
+                    None => ScenarioEvent::Skipped,
+                };
+                return;
             }
 
-            let mut world = Some(W::new().await.unwrap());
+            // Each attempt starts over with a fresh world.
+            loop {
+                let deadline = self.scenario_deadline(&feature, rule.as_deref(), &scenario);
 
-            let mut is_success = true;
+                let context = LifecycleContext {
+                    context: self.context(),
+                    feature: Rc::clone(&feature),
+                    rule: rule.clone(),
+                    scenario: Some(Rc::clone(&scenario)),
+                    outcome: None,
+                };
 
-            if let Some(steps) = feature.background.as_ref().map(|x| &x.steps) {
-                for step in steps.iter() {
-                    let this = Rc::clone(&self);
-                    let step = Rc::new(step.clone());
+                let mut is_success = true;
+                let mut failure = None;
+                let mut outcome = OutcomeCollector::default();
 
-                    yield ScenarioEvent::Background(Rc::clone(&step), StepEvent::Starting);
+                for (criteria, handler) in self.hooks.before.iter() {
+                    if !criteria.context().is_scenario() {
+                        continue;
+                    }
 
-                    let result = this.run_step(Rc::clone(&step), world.take().unwrap()).await;
+                    if criteria.eval(&feature, rule.as_deref(), Some(&*scenario)) {
+                        yield ScenarioEvent::Hook(HookType::Before, HookEvent::Starting);
+                        let hook = Box::pin((handler)(context.clone()).map(Ok));
+                        let (event, result) = self.run_hook(hook, deadline.clone()).await;
+                        let event = ScenarioEvent::Hook(HookType::Before, event);
+                        outcome.handle_scenario_event(&event);
+                        yield event;
+                        if let Err(kind) = result {
+                            failure = Some(kind);
+                            is_success = false;
+                            break;
+                        }
+                    }
+                }
 
-                    match result {
-                        TestEvent::Success(w, output) => {
-                            yield ScenarioEvent::Background(Rc::clone(&step), StepEvent::Passed(output));
-                            // Pass world result for current step to next step.
+                let mut world = None;
+                let mut teardown = None;
+                if is_success {
+                    match until(deadline.clone(), W::new()).await {
+                        Ok(Ok(w)) => {
+                            teardown = w.teardown();
                             world = Some(w);
                         }
-                        TestEvent::Failure(StepFailureKind::Panic(output, e)) => {
-                            yield ScenarioEvent::Background(Rc::clone(&step), StepEvent::Failed(StepFailureKind::Panic(output, e)));
-                            yield ScenarioEvent::Failed(FailureKind::Panic);
+                        Ok(Err(e)) => {
+                            failure = Some(FailureKind::WorldInit(error_chain(&e)));
                             is_success = false;
-                            break;
-                        },
-                        TestEvent::Failure(StepFailureKind::TimedOut) => {
-                            yield ScenarioEvent::Background(Rc::clone(&step), StepEvent::Failed(StepFailureKind::TimedOut));
-                            yield ScenarioEvent::Failed(FailureKind::TimedOut);
-                            is_success = false;
-                            break;
                         }
-                        TestEvent::Skipped => {
-                            yield ScenarioEvent::Background(Rc::clone(&step), StepEvent::Skipped);
-                            yield ScenarioEvent::Skipped;
+                        Err(kind) => {
+                            failure = Some(kind);
                             is_success = false;
-                            break;
                         }
-                        TestEvent::Unimplemented => {
-                            yield ScenarioEvent::Background(Rc::clone(&step), StepEvent::Unimplemented);
-                            yield ScenarioEvent::Skipped;
-                            is_success = false;
-                            break;
+                    }
+                }
+
+                if let Some(w) = world.as_mut().filter(|_| is_success) {
+                    for (criteria, handler) in self.hooks.before_world.iter() {
+                        if criteria.eval(&feature, rule.as_deref(), Some(&*scenario)) {
+                            yield ScenarioEvent::Hook(HookType::Before, HookEvent::Starting);
+                            let hook = Box::pin((handler)(w, context.clone()).map(Ok));
+                            let (event, result) = self.run_hook(hook, deadline.clone()).await;
+                            let event = ScenarioEvent::Hook(HookType::Before, event);
+                            outcome.handle_scenario_event(&event);
+                            yield event;
+                            if let Err(kind) = result {
+                                failure = Some(kind);
+                                is_success = false;
+                                break;
+                            }
                         }
                     }
                 }
-            }
 
-            if is_success {
-                for step in scenario.steps.iter() {
+                let mut is_skipped_scenario = false;
+                for (is_bg, step) in steps.iter() {
                     let this = Rc::clone(&self);
-
-                    let mut step = step.clone();
-                    if !example.is_empty() {
-                        step.value = example.insert_values(&step.value);
+                    let step_event = |event| if *is_bg {
+                        ScenarioEvent::Background(Rc::clone(step), event)
+                    } else {
+                        ScenarioEvent::Step(Rc::clone(step), event)
+                    };
+
+                    // Steps after a failed or skipped one are never run, but
+                    // are still reported so the scenario is shown in full.
+                    if !is_success {
+                        yield step_event(StepEvent::Skipped);
+                        continue;
                     }
-                    let step = Rc::new(step);
 
-                    yield ScenarioEvent::Step(Rc::clone(&step), StepEvent::Starting);
+                    yield step_event(StepEvent::Starting);
+
+                    let mut hooks_result = Ok(());
+                    for (criteria, handler) in self.hooks.before_step.iter() {
+                        if criteria.eval(&feature, rule.as_deref(), Some(&*scenario)) {
+                            yield ScenarioEvent::Hook(HookType::BeforeStep, HookEvent::Starting);
+                            let hook = (handler)(world.as_mut(), step, &StepEvent::Starting, context.clone());
+                            let (event, result) = self.run_hook(Box::pin(hook.map(Ok)), deadline.clone()).await;
+                            let event = ScenarioEvent::Hook(HookType::BeforeStep, event);
+                            outcome.handle_scenario_event(&event);
+                            yield event;
+                            if result.is_err() {
+                                hooks_result = result;
+                                break;
+                            }
+                        }
+                    }
 
-                    let result = this.run_step(Rc::clone(&step), world.take().unwrap()).await;
+                    let result = match hooks_result {
+                        Ok(()) => until(deadline.clone(), this.run_step(Rc::clone(step), world.take().unwrap()))
+                            .await
+                            .unwrap_or_else(TestEvent::Interrupted),
+                        Err(kind) => TestEvent::Interrupted(kind),
+                    };
 
-                    match result {
+                    let event = match result {
                         TestEvent::Success(w, output) => {
-                            yield ScenarioEvent::Step(Rc::clone(&step), StepEvent::Passed(output));
                             // Pass world result for current step to next step.
                             world = Some(w);
+                            StepEvent::Passed(output)
                         }
-                        TestEvent::Failure(StepFailureKind::Panic(output, e)) => {
-                            yield ScenarioEvent::Step(Rc::clone(&step), StepEvent::Failed(StepFailureKind::Panic(output, e)));
-                            yield ScenarioEvent::Failed(FailureKind::Panic);
-                            is_success = false;
-                            break;
-                        },
-                        TestEvent::Failure(StepFailureKind::TimedOut) => {
-                            yield ScenarioEvent::Step(Rc::clone(&step), StepEvent::Failed(StepFailureKind::TimedOut));
-                            yield ScenarioEvent::Failed(FailureKind::TimedOut);
-                            is_success = false;
-                            break;
+                        TestEvent::Failure(kind) => {
+                            failure = Some(match kind {
+                                StepFailureKind::Panic(..) => FailureKind::Panic,
+                                StepFailureKind::TimedOut => FailureKind::TimedOut,
+                            });
+                            StepEvent::Failed(kind)
                         }
-                        TestEvent::Skipped => {
-                            yield ScenarioEvent::Step(Rc::clone(&step), StepEvent::Skipped);
-                            yield ScenarioEvent::Skipped;
-                            is_success = false;
-                            break;
+                        TestEvent::Interrupted(kind) => {
+                            let event = match kind {
+                                FailureKind::ScenarioTimedOut | FailureKind::RunTimedOut => StepEvent::Failed(StepFailureKind::TimedOut),
+                                // A hook before the step failed, so it never ran.
+                                _ => StepEvent::Skipped,
+                            };
+                            failure = Some(kind);
+                            event
                         }
-                        TestEvent::Unimplemented => {
-                            yield ScenarioEvent::Step(Rc::clone(&step), StepEvent::Unimplemented);
-                            yield ScenarioEvent::Skipped;
+                        TestEvent::Ambiguous(patterns) => {
+                            if self.fail_on_ambiguous_steps {
+                                failure = Some(FailureKind::Ambiguous);
+                            }
+                            StepEvent::Ambiguous(patterns)
+                        }
+                        TestEvent::Skipped => StepEvent::Skipped,
+                        TestEvent::Unimplemented => StepEvent::Unimplemented,
+                    };
+                    let is_skipped = failure.is_none() && matches!(event, StepEvent::Skipped | StepEvent::Unimplemented | StepEvent::Ambiguous(_));
+
+                    let scenario_event = step_event(event.clone());
+                    outcome.handle_scenario_event(&scenario_event);
+                    yield scenario_event;
+
+                    let mut hooks_result = Ok(());
+                    for (criteria, handler) in self.hooks.after_step.iter() {
+                        if criteria.eval(&feature, rule.as_deref(), Some(&*scenario)) {
+                            yield ScenarioEvent::Hook(HookType::AfterStep, HookEvent::Starting);
+                            let hook = (handler)(world.as_mut(), step, &event, context.clone());
+                            let (event, result) = self.run_hook(Box::pin(hook.map(Ok)), deadline.clone()).await;
+                            let event = ScenarioEvent::Hook(HookType::AfterStep, event);
+                            outcome.handle_scenario_event(&event);
+                            yield event;
+                            if result.is_err() {
+                                hooks_result = result;
+                                break;
+                            }
+                        }
+                    }
+
+                    match hooks_result {
+                        Err(kind) => {
+                            failure.get_or_insert(kind);
+                        }
+                        Ok(()) if is_skipped => is_skipped_scenario = true,
+                        Ok(()) => {}
+                    }
+                    if failure.is_some() || is_skipped {
+                        is_success = false;
+                    }
+                }
+
+                // Once the deadline has passed, the after hooks get a short
+                // grace period of their own to clean up and report in.
+                let after_deadline = match failure {
+                    Some(ref kind @ FailureKind::ScenarioTimedOut) | Some(ref kind @ FailureKind::RunTimedOut) => {
+                        Some((Instant::now() + AFTER_HOOK_GRACE_PERIOD, kind.clone()))
+                    }
+                    _ => deadline.clone(),
+                };
+
+                match failure {
+                    Some(ref kind) => outcome.fail(kind.clone()),
+                    None if is_skipped_scenario => outcome.handle_scenario_event(&ScenarioEvent::Skipped),
+                    None if is_success => outcome.handle_scenario_event(&ScenarioEvent::Passed),
+                    None => {}
+                }
+                let context = LifecycleContext {
+                    outcome: Some(outcome.outcome()),
+                    ..context
+                };
+                if let Some(w) = world.as_mut() {
+                    for (criteria, handler) in self.hooks.after_world.iter() {
+                        if criteria.eval(&feature, rule.as_deref(), Some(&*scenario)) {
+                            yield ScenarioEvent::Hook(HookType::After, HookEvent::Starting);
+                            let hook = Box::pin((handler)(w, context.clone()).map(Ok));
+                            let (event, result) = self.run_hook(hook, after_deadline.clone()).await;
+                            yield ScenarioEvent::Hook(HookType::After, event);
+                            if let Err(kind) = result {
+                                failure.get_or_insert(kind);
+                                is_success = false;
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                for (criteria, handler) in self.hooks.after.iter() {
+                    if !criteria.context().is_scenario() {
+                        continue;
+                    }
+
+                    if criteria.eval(&feature, rule.as_deref(), Some(&*scenario)) {
+                        yield ScenarioEvent::Hook(HookType::After, HookEvent::Starting);
+                        let hook = Box::pin((handler)(context.clone()).map(Ok));
+                        let (event, result) = self.run_hook(hook, after_deadline.clone()).await;
+                        yield ScenarioEvent::Hook(HookType::After, event);
+                        if let Err(kind) = result {
+                            failure.get_or_insert(kind);
                             is_success = false;
                             break;
                         }
                     }
                 }
-            }
 
-            for (criteria, handler) in self.after.iter() {
-                if !criteria.context().is_scenario() {
-                    continue;
+                // A world that made it through the scenario knows everything
+                // its steps set up. One consumed by a failing step falls back
+                // on the cleanup asked for when it was created.
+                let teardown = match world.take() {
+                    Some(w) => w.teardown(),
+                    None => teardown,
+                };
+
+                // Cleanup is only cut short by the run timeout, and even then
+                // gets the same grace period as the after hooks, as skipping
+                // it could leave resources behind for the scenarios that follow.
+                if let Some(teardown) = teardown {
+                    let teardown_deadline = self.run_deadline().map(|(at, kind)| {
+                        (at.max(Instant::now()) + AFTER_HOOK_GRACE_PERIOD, kind)
+                    });
+                    match until(teardown_deadline, teardown).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => {
+                            yield ScenarioEvent::TeardownFailed(error_chain(&e));
+                            if failure.is_none() && is_success {
+                                failure = Some(FailureKind::Teardown);
+                            }
+                        }
+                        Err(kind) => {
+                            yield ScenarioEvent::TeardownFailed(
+                                vec!["teardown did not finish before the run timed out".to_string()],
+                            );
+                            failure.get_or_insert(kind);
+                            is_success = false;
+                        }
+                    }
                 }
 
-                if criteria.eval(&*feature, rule.as_ref().map(|x| &**x), Some(&*scenario)) {
-                    (handler)(context.clone()).await;
+                // Only now that the after hooks are done is it known how the
+                // scenario ended. A skipped scenario would only be skipped
+                // again, so it is never retried.
+                match failure {
+                    Some(FailureKind::RunTimedOut) => {
+                        yield ScenarioEvent::Failed(FailureKind::RunTimedOut);
+                        break;
+                    }
+                    Some(_) if attempt < retries && !is_skipped_scenario => {
+                        attempt += 1;
+                        yield ScenarioEvent::Retrying(attempt);
+                    }
+                    Some(kind) => {
+                        yield ScenarioEvent::Failed(kind);
+                        break;
+                    }
+                    None if is_skipped_scenario => {
+                        yield ScenarioEvent::Skipped;
+                        break;
+                    }
+                    None => {
+                        if is_success {
+                            yield ScenarioEvent::Passed;
+                        }
+                        break;
+                    }
                 }
             }
+        };
 
-            if is_success {
-                yield ScenarioEvent::Passed;
+        Box::pin(stream.inspect(move |event| {
+            if this.fail_fast {
+                if let ScenarioEvent::Failed(_) = event {
+                    this.stopped.set(true);
+                }
             }
-        })
+        }))
     }
 
     pub fn run(self: Rc<Self>) -> CucumberStream {
         Box::pin(stream! {
             let mut stats = StatsCollector::new();
+            self.run_deadline.set(self.run_timeout.map(|t| Instant::now() + t));
             yield CucumberEvent::Starting;
 
-            let features = self.features.iter().cloned().map(Rc::new).collect::<Vec<_>>();
-            for feature in features.into_iter() {
+            // Nothing else can get hold of the context while it is taken out.
+            let mut context = self.context.replace(Rc::new(Context::new()));
+            let mut is_set_up = true;
+            for hook in self.hooks.before_all.iter() {
+                let event = HookEvent::Starting;
+                stats.handle_hook_event(&event);
+                yield CucumberEvent::Hook(HookType::Before, event);
+
+                let hook = match Rc::get_mut(&mut context) {
+                    Some(context) => (hook)(context),
+                    None => unreachable!("context is not shared before the run"),
+                };
+                let hook = Box::pin(hook.map_err(|e| error_chain(&*e)));
+                let (event, result) = self.run_hook(hook, self.run_deadline()).await;
+                stats.handle_hook_event(&event);
+                is_set_up = result.is_ok();
+                yield CucumberEvent::Hook(HookType::Before, event);
+
+                if !is_set_up {
+                    break;
+                }
+            }
+            self.context.replace(context);
+
+            let features = self.features.iter().filter(|_| is_set_up);
+            let streams = features.cloned().map(Rc::new).map(|feature| {
                 let this = Rc::clone(&self);
-                let mut stream = this.run_feature(Rc::clone(&feature));
+                let stream = this.run_feature(Rc::clone(&feature));
+                stream.map(move |event| (Rc::clone(&feature), event))
+            }).collect::<Vec<_>>();
 
-                while let Some(event) = stream.next().await {
-                    stats.handle_feature_event(&event);
-                    yield CucumberEvent::Feature(Rc::clone(&feature), event);
-                }
+            let mut stream = ordered_merge(futures::stream::iter(streams), self.max_concurrent_scenarios);
+
+            while let Some((feature, event)) = stream.next().await {
+                stats.handle_feature_event(&event);
+                yield CucumberEvent::Feature(feature, event);
+            }
+
+            for hook in self.hooks.after_all.iter() {
+                let event = HookEvent::Starting;
+                stats.handle_hook_event(&event);
+                yield CucumberEvent::Hook(HookType::After, event);
+
+                let context = self.context();
+                let hook = Box::pin((hook)(&context).map_err(|e| error_chain(&*e)));
+                let (event, _) = self.run_hook(hook, self.run_deadline()).await;
+                stats.handle_hook_event(&event);
+                yield CucumberEvent::Hook(HookType::After, event);
             }
 
-            yield CucumberEvent::Finished(stats.collect());
+            let mut result = stats.collect();
+            result.scenarios.not_run = self.not_run.get();
+            result.seed = self.seed;
+            yield CucumberEvent::Finished(result);
         })
     }
 }
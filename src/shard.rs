@@ -0,0 +1,73 @@
+// Copyright (c) 2018-2021  Brendan Molloy <brendan@bbqsrc.net>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::Path;
+use std::str::FromStr;
+
+/// One of `total` disjoint parts of the scenarios, numbered from 1.
+///
+/// Scenarios are assigned to a shard by a hash of their feature file path,
+/// relative to the current directory, and their line, so the assignment is
+/// the same on every machine and in every run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    index: u32,
+    total: u32,
+}
+
+impl Shard {
+    /// Returns `None` unless `1 <= index <= total`.
+    pub fn new(index: u32, total: u32) -> Option<Shard> {
+        if index >= 1 && index <= total {
+            Some(Shard { index, total })
+        } else {
+            None
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    /// Whether the scenario at `line` of the feature file at `path` belongs
+    /// to this shard.
+    pub(crate) fn contains(&self, path: Option<&Path>, line: usize) -> bool {
+        let path = path
+            .map(|path| {
+                std::env::current_dir()
+                    .ok()
+                    .and_then(|dir| pathdiff::diff_paths(path, dir))
+                    .unwrap_or_else(|| path.to_path_buf())
+            })
+            .unwrap_or_default();
+
+        // FNV-1a, as the hashers of std are not guaranteed to be stable.
+        let key = format!("{}:{}", path.to_string_lossy().replace('\\', "/"), line);
+        let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        hash % self.total as u64 == (self.index - 1) as u64
+    }
+}
+
+/// Parses `INDEX/TOTAL`, e.g. `2/4`.
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid shard '{}', expected INDEX/TOTAL, e.g. 1/4", s);
+        let (index, total) = s.split_once('/').ok_or_else(invalid)?;
+        let index = index.trim().parse().map_err(|_| invalid())?;
+        let total = total.trim().parse().map_err(|_| invalid())?;
+        Shard::new(index, total).ok_or_else(invalid)
+    }
+}
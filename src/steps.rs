@@ -26,7 +26,7 @@ impl<W: World> Steps<W> {
     }
 
     fn insert_async(&mut self, ty: StepType, name: &'static str, test_fn: StepFn<W>) -> &mut Self {
-        self.steps.insert_basic(ty, name, test_fn.into());
+        self.steps.insert_basic(ty, name, test_fn);
         self
     }
 
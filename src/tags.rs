@@ -0,0 +1,65 @@
+// Copyright (c) 2018-2021  Brendan Molloy <brendan@bbqsrc.net>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Duration;
+
+use gherkin::tagexpr::TagOperation;
+use gherkin::{Feature, Rule, Scenario};
+
+/// Tags of the feature, the rule and the scenario, in that order.
+///
+/// Tags are stored without their leading `@`.
+pub(crate) fn inherited<'a>(
+    feature: &'a Feature,
+    rule: Option<&'a Rule>,
+    scenario: Option<&'a Scenario>,
+) -> impl Iterator<Item = &'a String> {
+    feature
+        .tags
+        .iter()
+        .chain(rule.into_iter().flat_map(|r| r.tags.iter()))
+        .chain(scenario.into_iter().flat_map(|s| s.tags.iter()))
+}
+
+/// Evaluates a tag expression against a set of tags.
+pub(crate) fn eval(op: &TagOperation, tags: &[&String]) -> bool {
+    match op {
+        TagOperation::And(a, b) => eval(a, tags) && eval(b, tags),
+        TagOperation::Or(a, b) => eval(a, tags) || eval(b, tags),
+        TagOperation::Not(a) => !eval(a, tags),
+        TagOperation::Tag(tag) => tags.contains(&tag),
+    }
+}
+
+/// The argument of a parameterised tag such as `@retry-3`, taking the last
+/// (i.e. most specific) occurrence.
+///
+/// Gherkin only allows letters, digits, `-` and `_` in tag names, so the
+/// argument follows the name after a `-`.
+pub(crate) fn value<'a>(tags: impl Iterator<Item = &'a String>, name: &str) -> Option<&'a str> {
+    tags.filter_map(|tag| tag.strip_prefix(name)?.strip_prefix('-'))
+        .last()
+}
+
+/// Parses a duration written as a number followed by `ms`, `s`, `m` or `h`,
+/// where a bare number is taken as seconds.
+pub(crate) fn duration(value: &str) -> Option<Duration> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<u64>().ok()?;
+
+    match unit {
+        "ms" => Some(Duration::from_millis(number)),
+        "" | "s" => Some(Duration::from_secs(number)),
+        "m" => number.checked_mul(60).map(Duration::from_secs),
+        "h" => number.checked_mul(60 * 60).map(Duration::from_secs),
+        _ => None,
+    }
+}
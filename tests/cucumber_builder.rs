@@ -101,7 +101,7 @@ async fn main() {
     // e.g. setup_some_db_thing()?;
 
     cucumber::Cucumber::<MyWorld>::new()
-        .features(&["./features/basic"])
+        .features(["./features/basic"])
         .steps(example_steps::steps())
         .context(
             cucumber::Context::new()
@@ -112,7 +112,6 @@ async fn main() {
         .before(criteria::scenario(Regex::new(".*").unwrap()), |_| {
             async move {
                 println!("S:AHHHH");
-                ()
             }
             .boxed()
         })
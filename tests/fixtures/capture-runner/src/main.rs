@@ -10,7 +10,7 @@ impl World for CaptureRunnerWorld {
     type Error = std::convert::Infallible;
 
     async fn new() -> Result<Self, Self::Error> {
-        Ok(CaptureRunnerWorld::default())
+        Ok(CaptureRunnerWorld)
     }
 }
 /// Event handler that delegates for printing to the default event handler,
@@ -91,7 +91,7 @@ fn main() {
     let event_handler = ProblemDetectingEventHandler::default();
     let runner = Cucumber::with_handler(event_handler.clone())
         .steps(steps)
-        .features(&["./features/capture"])
+        .features(["./features/capture"])
         .enable_capture(enable_capture);
 
     futures::executor::block_on(runner.run());
@@ -1,8 +1,12 @@
 use async_trait::async_trait;
-use cucumber_rust::{event::*, t, Cucumber, EventHandler, Steps, World};
+use cucumber_rust::{
+    criteria, event::*, output::Snippet, t, Cucumber, EventHandler, LifecycleContext, Order,
+    Outcome, Shard, Steps, Teardown, World,
+};
 use serial_test::serial;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -80,6 +84,28 @@ impl EventHandler for CustomEventHandler {
     }
 }
 
+/// Records whatever `record` picks out of the events of a run.
+#[derive(Clone)]
+struct RecordingHandler<T> {
+    record: fn(&CucumberEvent) -> Option<T>,
+    records: Arc<Mutex<Vec<T>>>,
+}
+impl<T> RecordingHandler<T> {
+    fn new(record: fn(&CucumberEvent) -> Option<T>) -> Self {
+        RecordingHandler {
+            record,
+            records: Arc::default(),
+        }
+    }
+}
+impl<T: 'static> EventHandler for RecordingHandler<T> {
+    fn handle_event(&mut self, event: &CucumberEvent) {
+        if let Some(record) = (self.record)(event) {
+            self.records.lock().unwrap().push(record);
+        }
+    }
+}
+
 #[derive(Default)]
 struct StatelessWorld;
 
@@ -88,10 +114,21 @@ impl World for StatelessWorld {
     type Error = std::convert::Infallible;
 
     async fn new() -> Result<Self, Self::Error> {
-        Ok(StatelessWorld::default())
+        Ok(StatelessWorld)
+    }
+
+    fn teardown(&self) -> Option<Teardown<Self::Error>> {
+        Some(Box::pin(async {
+            futures_timer::Delay::new(Duration::from_millis(1)).await;
+            TEARDOWNS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }))
     }
 }
 
+static FLAKY_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+static TEARDOWNS: AtomicUsize = AtomicUsize::new(0);
+
 fn stateless_steps() -> Steps<StatelessWorld> {
     let mut steps = Steps::<StatelessWorld>::new();
     steps.when("something", |world, _step| world);
@@ -100,6 +137,19 @@ fn stateless_steps() -> Steps<StatelessWorld> {
     steps.then("it's not okay", |_world, _step| {
         panic!("Intentionally panicking to fail the step")
     });
+    steps.then("it's flaky", |world, _step| {
+        if FLAKY_ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("Intentionally panicking on the first attempt")
+        }
+        world
+    });
+    steps.when_async(
+        "something slow happens",
+        t!(|world, _step| {
+            futures_timer::Delay::new(Duration::from_millis(500)).await;
+            world
+        }),
+    );
     steps.then_async(
         "it takes a long time",
         t!(|world, _step| {
@@ -117,14 +167,15 @@ fn user_defined_event_handlers_are_expressible() {
 
     let runner = Cucumber::with_handler(custom_handler.clone())
         .steps(stateless_steps())
-        .features(&["./features/integration"])
+        .features(["./features/integration"])
         .step_timeout(Duration::from_secs(1));
 
     let results = futures::executor::block_on(runner.run());
 
     assert_eq!(results.features.total, 1);
     assert_eq!(results.scenarios.total, 4);
-    assert_eq!(results.steps.total, 14);
+    assert_eq!(results.steps.total, 8);
+    assert_eq!(results.steps.skipped, 2);
     assert_eq!(results.steps.passed, 4);
     assert_eq!(results.scenarios.failed, 1);
 
@@ -137,6 +188,1372 @@ fn user_defined_event_handlers_are_expressible() {
     assert!(handler_state.any_step_timeouts);
 }
 
+fn scenario_progress(event: &CucumberEvent) -> Option<String> {
+    let (scenario, event) = match event {
+        CucumberEvent::Feature(_, FeatureEvent::Scenario(scenario, event)) => (scenario, event),
+        _ => return None,
+    };
+    let name = match event {
+        ScenarioEvent::Starting(_) => "starting",
+        ScenarioEvent::Passed => "passed",
+        _ => return None,
+    };
+    Some(format!("{} {}", name, scenario.name))
+}
+
+#[test]
+#[serial]
+fn scenarios_run_concurrently_in_order() {
+    let handler = RecordingHandler::new(scenario_progress);
+
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/concurrency"])
+        .max_concurrent_scenarios(4);
+
+    let started = std::time::Instant::now();
+    let results = futures::executor::block_on(runner.run());
+
+    // Run one after another, the four scenarios would take at least 2s.
+    assert!(started.elapsed() < Duration::from_millis(2000));
+    assert_eq!(results.scenarios.total, 4);
+    assert_eq!(results.scenarios.passed, 4);
+
+    let events = handler.records.lock().unwrap();
+    let expected = ["first", "second", "third", "fourth"]
+        .iter()
+        .flat_map(|n| {
+            vec![
+                format!("starting The {} slow scenario", n),
+                format!("passed The {} slow scenario", n),
+            ]
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(*events, expected);
+}
+
+static TIMELINE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn record_timeline(ctx: &LifecycleContext, what: &str) {
+    let name = match ctx.scenario {
+        Some(ref scenario) => &scenario.name,
+        None => &ctx.feature.name,
+    };
+    TIMELINE.lock().unwrap().push(format!("{} {}", what, name));
+}
+
+#[test]
+#[serial]
+fn serial_scenarios_run_exclusively() {
+    TIMELINE.lock().unwrap().clear();
+
+    let any = || criteria::scenario(regex::Regex::new(".*").unwrap());
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/serial"])
+        .max_concurrent_scenarios(4)
+        .before(any(), |ctx| {
+            record_timeline(&ctx, "start");
+            Box::pin(async {})
+        })
+        .after(any(), |ctx| {
+            record_timeline(&ctx, "end");
+            Box::pin(async {})
+        })
+        // Still running when the scenario before it is done, unless it has
+        // to wait for the serial scenario to finish.
+        .after(
+            criteria::feature("Scenarios running alongside serial ones"),
+            |ctx| {
+                Box::pin(async move {
+                    record_timeline(&ctx, "start");
+                    futures_timer::Delay::new(Duration::from_millis(200)).await;
+                    record_timeline(&ctx, "end");
+                })
+            },
+        );
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 4);
+    let timeline = TIMELINE.lock().unwrap();
+    let start = timeline
+        .iter()
+        .position(|e| e == "start A slow scenario on its own")
+        .unwrap();
+    assert_eq!(timeline[start + 1], "end A slow scenario on its own");
+    assert_eq!(timeline.len(), 10);
+}
+
+#[test]
+#[serial]
+fn tag_expressions_select_scenarios() {
+    let handler = RecordingHandler::new(scenario_progress);
+
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/tags"])
+        .filter_tags("@smoke and not (@wip or @slow)");
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 2);
+    assert_eq!(results.scenarios.passed, 2);
+    assert_eq!(results.rules.total, 1);
+}
+
+#[test]
+#[serial]
+fn fail_fast_stops_after_first_failure() {
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .fail_fast(true);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 2);
+    assert_eq!(results.scenarios.failed, 1);
+    assert_eq!(results.scenarios.not_run, 2);
+    assert!(results.failed());
+}
+
+#[test]
+#[serial]
+fn retried_scenarios_are_flaky() {
+    FLAKY_ATTEMPTS.store(0, Ordering::SeqCst);
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/retry"]);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(FLAKY_ATTEMPTS.load(Ordering::SeqCst), 2);
+    assert_eq!(results.scenarios.total, 1);
+    assert_eq!(results.scenarios.flaky, 1);
+    assert_eq!(results.scenarios.passed, 0);
+    assert!(!results.failed());
+}
+
+#[test]
+#[serial]
+fn scenario_timeout_bounds_whole_scenario() {
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_timeout(Duration::from_millis(500));
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 4);
+    assert_eq!(results.scenarios.failed, 1);
+    assert_eq!(results.scenarios.timed_out, 1);
+}
+
+#[test]
+#[serial]
+fn run_timeout_leaves_remaining_scenarios_not_run() {
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/concurrency", "./features/serial/serial.feature"])
+        .run_timeout(Duration::from_millis(700));
+
+    let results = futures::executor::block_on(runner.run());
+
+    // The serial feature sorts first, so the slow one is never started.
+    assert_eq!(results.scenarios.passed, 1);
+    assert_eq!(results.scenarios.timed_out, 1);
+    assert_eq!(results.scenarios.not_run, 1 + 4);
+    assert_eq!(results.features.not_run, 1);
+}
+
+#[derive(Debug)]
+struct WorldInitError(std::io::Error);
+
+impl std::fmt::Display for WorldInitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("could not connect")
+    }
+}
+
+impl std::error::Error for WorldInitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+struct FailingWorld;
+
+#[async_trait(?Send)]
+impl World for FailingWorld {
+    type Error = WorldInitError;
+
+    async fn new() -> Result<Self, Self::Error> {
+        Err(WorldInitError(std::io::Error::new(
+            std::io::ErrorKind::ConnectionRefused,
+            "connection refused",
+        )))
+    }
+}
+
+fn world_init_failure(event: &CucumberEvent) -> Option<Vec<String>> {
+    match event {
+        CucumberEvent::Feature(
+            _,
+            FeatureEvent::Scenario(_, ScenarioEvent::Failed(FailureKind::WorldInit(chain))),
+        ) => Some(chain.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn world_init_errors_fail_scenarios() {
+    let handler = RecordingHandler::new(world_init_failure);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(Steps::<FailingWorld>::new())
+        .features(["./features/concurrency"]);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 4);
+    assert_eq!(results.scenarios.failed, 4);
+    let failures = handler.records.lock().unwrap();
+    assert_eq!(failures.len(), 4);
+    assert_eq!(failures[0], ["could not connect", "connection refused"]);
+}
+
+#[test]
+#[serial]
+fn worlds_are_torn_down_whatever_the_outcome() {
+    TEARDOWNS.store(0, Ordering::SeqCst);
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_timeout(Duration::from_millis(500));
+
+    futures::executor::block_on(runner.run());
+
+    assert_eq!(TEARDOWNS.load(Ordering::SeqCst), 4);
+}
+
+struct LeakyWorld;
+
+#[async_trait(?Send)]
+impl World for LeakyWorld {
+    type Error = std::io::Error;
+
+    async fn new() -> Result<Self, Self::Error> {
+        Ok(LeakyWorld)
+    }
+
+    fn teardown(&self) -> Option<Teardown<Self::Error>> {
+        Some(Box::pin(async {
+            Err(std::io::Error::other("could not stop server"))
+        }))
+    }
+}
+
+fn teardown_failure(event: &CucumberEvent) -> Option<Vec<String>> {
+    match event {
+        CucumberEvent::Feature(
+            _,
+            FeatureEvent::Scenario(_, ScenarioEvent::TeardownFailed(chain)),
+        ) => Some(chain.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn teardown_errors_fail_scenarios() {
+    let mut steps = Steps::<LeakyWorld>::new();
+    steps.when("something", |world, _step| world);
+    steps.then("it's okay", |world, _step| world);
+
+    let handler = RecordingHandler::new(teardown_failure);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(steps)
+        .features(["./features/integration"])
+        .scenario_regex("^A successful scenario$");
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 1);
+    assert_eq!(results.scenarios.failed, 1);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        [["could not stop server"]]
+    );
+}
+
+struct HangingWorld;
+
+#[async_trait(?Send)]
+impl World for HangingWorld {
+    type Error = std::io::Error;
+
+    async fn new() -> Result<Self, Self::Error> {
+        Ok(HangingWorld)
+    }
+
+    fn teardown(&self) -> Option<Teardown<Self::Error>> {
+        Some(Box::pin(futures::future::pending()))
+    }
+}
+
+#[test]
+#[serial]
+fn hanging_teardown_is_cut_short_after_the_run_timeout() {
+    let mut steps = Steps::<HangingWorld>::new();
+    steps.when("something", |world, _step| world);
+    steps.then("it's okay", |world, _step| world);
+
+    let handler = RecordingHandler::new(teardown_failure);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(steps)
+        .features(["./features/integration"])
+        .scenario_regex("^A successful scenario$")
+        .run_timeout(Duration::from_millis(100));
+
+    let started = std::time::Instant::now();
+    let results = futures::executor::block_on(runner.run());
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(results.scenarios.timed_out, 1);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        [["teardown did not finish before the run timed out"]]
+    );
+}
+
+struct ProcessWorld {
+    pid: Option<usize>,
+}
+
+static KILLED_PID: AtomicUsize = AtomicUsize::new(0);
+
+#[async_trait(?Send)]
+impl World for ProcessWorld {
+    type Error = std::io::Error;
+
+    async fn new() -> Result<Self, Self::Error> {
+        Ok(ProcessWorld { pid: None })
+    }
+
+    fn teardown(&self) -> Option<Teardown<Self::Error>> {
+        let pid = self.pid?;
+        Some(Box::pin(async move {
+            KILLED_PID.store(pid, Ordering::SeqCst);
+            Ok(())
+        }))
+    }
+}
+
+#[test]
+#[serial]
+fn teardown_sees_what_the_steps_set_up() {
+    KILLED_PID.store(0, Ordering::SeqCst);
+
+    let mut steps = Steps::<ProcessWorld>::new();
+    steps.when("something", |mut world, _step| {
+        world.pid = Some(1234);
+        world
+    });
+    steps.then("it's okay", |world, _step| world);
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(steps)
+        .features(["./features/integration"])
+        .scenario_regex("^A successful scenario$");
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 1);
+    assert_eq!(KILLED_PID.load(Ordering::SeqCst), 1234);
+}
+
+#[derive(Default)]
+struct CountingWorld {
+    steps: usize,
+}
+
+#[async_trait(?Send)]
+impl World for CountingWorld {
+    type Error = std::convert::Infallible;
+
+    async fn new() -> Result<Self, Self::Error> {
+        Ok(CountingWorld::default())
+    }
+}
+
+static COUNTED_STEPS: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+#[serial]
+fn world_hooks_access_the_world() {
+    COUNTED_STEPS.store(0, Ordering::SeqCst);
+
+    let mut steps = Steps::<CountingWorld>::new();
+    steps.when("something", |mut world, _step| {
+        world.steps += 1;
+        world
+    });
+    steps.then("it's okay", |mut world, _step| {
+        world.steps += 1;
+        world
+    });
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(steps)
+        .features(["./features/integration"])
+        .scenario_regex("^A successful scenario$")
+        .before_world(
+            criteria::scenario("A successful scenario"),
+            |world, _ctx| {
+                Box::pin(async move {
+                    world.steps = 10;
+                })
+            },
+        )
+        .after_world(
+            criteria::scenario("A successful scenario"),
+            |world, _ctx| {
+                Box::pin(async move {
+                    COUNTED_STEPS.store(world.steps, Ordering::SeqCst);
+                })
+            },
+        );
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 1);
+    assert_eq!(COUNTED_STEPS.load(Ordering::SeqCst), 12);
+}
+
+#[test]
+#[serial]
+fn after_world_hooks_only_see_worlds_that_survived() {
+    OUTCOMES.lock().unwrap().clear();
+
+    let mut steps = Steps::<CountingWorld>::new();
+    steps.when("something", |mut world, _step| {
+        world.steps += 1;
+        world
+    });
+    steps.when("another thing", |mut world, _step| {
+        world.steps += 1;
+        world
+    });
+    steps.then("it's okay", |mut world, _step| {
+        world.steps += 1;
+        world
+    });
+    steps.then("it's not okay", |_world, _step| {
+        panic!("Intentionally panicking to fail the step")
+    });
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(steps)
+        .features(["./features/integration"])
+        .scenario_regex("^A (successful|failing) scenario$")
+        .after_step(
+            criteria::scenario("A successful scenario"),
+            |_world, step, _event, _ctx| {
+                let is_last = step.value == "it's okay";
+                Box::pin(async move {
+                    if is_last {
+                        panic!("could not save the recording")
+                    }
+                })
+            },
+        )
+        .after_world(
+            criteria::scenario(regex::Regex::new(".*").unwrap()),
+            |world, ctx| {
+                let entry = format!("{} after {} steps", describe_outcome(&ctx), world.steps);
+                Box::pin(async move { OUTCOMES.lock().unwrap().push(entry) })
+            },
+        );
+
+    let results = futures::executor::block_on(runner.run());
+
+    // The failing scenario's world went down with its panicking step, while
+    // the one failed by a hook is still around to be inspected.
+    assert_eq!(results.scenarios.failed, 2);
+    assert_eq!(
+        *OUTCOMES.lock().unwrap(),
+        ["A successful scenario: failed at None \
+             with Some(\"could not save the recording\") after 2 steps"]
+    );
+}
+
+static STEP_HOOKS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[test]
+#[serial]
+fn step_hooks_see_every_step_and_its_outcome() {
+    STEP_HOOKS.lock().unwrap().clear();
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_regex("^A failing scenario$")
+        .before_step(
+            criteria::scenario("A failing scenario"),
+            |world, step, _event, _ctx| {
+                let entry = format!("before {} {}", step.value, world.is_some());
+                Box::pin(async move { STEP_HOOKS.lock().unwrap().push(entry) })
+            },
+        )
+        .after_step(
+            criteria::scenario("A failing scenario"),
+            |world, step, event, _ctx| {
+                let outcome = match event {
+                    StepEvent::Passed(_) => "passed",
+                    StepEvent::Failed(_) => "failed",
+                    _ => "other",
+                };
+                let entry = format!("after {} {} {}", step.value, outcome, world.is_some());
+                Box::pin(async move { STEP_HOOKS.lock().unwrap().push(entry) })
+            },
+        );
+
+    futures::executor::block_on(runner.run());
+
+    assert_eq!(
+        *STEP_HOOKS.lock().unwrap(),
+        [
+            "before another thing true",
+            "after another thing passed true",
+            "before it's not okay true",
+            "after it's not okay failed false",
+        ]
+    );
+}
+
+#[test]
+#[serial]
+fn failing_before_step_hooks_skip_the_step() {
+    STEP_HOOKS.lock().unwrap().clear();
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_regex("^A successful scenario$")
+        .before_step(
+            criteria::scenario("A successful scenario"),
+            |_world, _step, _event, _ctx| Box::pin(async { panic!("no connection to record on") }),
+        )
+        .after_step(
+            criteria::scenario("A successful scenario"),
+            |world, step, event, _ctx| {
+                let outcome = match event {
+                    StepEvent::Skipped => "skipped",
+                    StepEvent::Failed(_) => "failed",
+                    _ => "other",
+                };
+                let entry = format!("after {} {} {}", step.value, outcome, world.is_some());
+                Box::pin(async move { STEP_HOOKS.lock().unwrap().push(entry) })
+            },
+        );
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.failed, 1);
+    assert_eq!(results.steps.skipped, 2);
+    assert_eq!(results.steps.timed_out, 0);
+    assert_eq!(
+        *STEP_HOOKS.lock().unwrap(),
+        ["after something skipped true"]
+    );
+}
+
+static STEP_HOOK_ORDER: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn step_hook_order(event: &CucumberEvent) -> Option<()> {
+    let entry = match event {
+        CucumberEvent::Feature(_, FeatureEvent::Scenario(_, event)) => match event {
+            ScenarioEvent::Hook(HookType::BeforeStep, HookEvent::Starting) => "before starting",
+            ScenarioEvent::Hook(HookType::BeforeStep, HookEvent::Passed) => "before done",
+            ScenarioEvent::Hook(HookType::AfterStep, HookEvent::Starting) => "after starting",
+            ScenarioEvent::Hook(HookType::AfterStep, HookEvent::Passed) => "after done",
+            ScenarioEvent::Step(_, StepEvent::Passed(_)) => "step passed",
+            _ => return None,
+        },
+        _ => return None,
+    };
+    STEP_HOOK_ORDER.lock().unwrap().push(entry.to_string());
+    None
+}
+
+#[test]
+#[serial]
+fn step_hooks_are_reported_as_they_start() {
+    STEP_HOOK_ORDER.lock().unwrap().clear();
+
+    let runner = Cucumber::with_handler(RecordingHandler::new(step_hook_order))
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_regex("^A successful scenario$")
+        .before_step(
+            criteria::scenario("A successful scenario"),
+            |_world, _step, _event, _ctx| {
+                Box::pin(async {
+                    STEP_HOOK_ORDER
+                        .lock()
+                        .unwrap()
+                        .push("before ran".to_string())
+                })
+            },
+        )
+        .after_step(
+            criteria::scenario("A successful scenario"),
+            |_world, _step, _event, _ctx| {
+                Box::pin(async {
+                    STEP_HOOK_ORDER
+                        .lock()
+                        .unwrap()
+                        .push("after ran".to_string())
+                })
+            },
+        );
+
+    futures::executor::block_on(runner.run());
+
+    let expected = [
+        "before starting",
+        "before ran",
+        "before done",
+        "step passed",
+        "after starting",
+        "after ran",
+        "after done",
+    ];
+    assert_eq!(
+        *STEP_HOOK_ORDER.lock().unwrap(),
+        [expected, expected].concat()
+    );
+}
+
+struct ServerPort(u16);
+
+static TORN_DOWN_PORT: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+#[serial]
+fn run_hooks_set_up_the_shared_context() {
+    TORN_DOWN_PORT.store(0, Ordering::SeqCst);
+
+    let mut steps = Steps::<StatelessWorld>::new();
+    steps.when("something", |world, ctx| {
+        assert_eq!(ctx.get::<ServerPort>().unwrap().0, 4000);
+        world
+    });
+    steps.then("it's okay", |world, _step| world);
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(steps)
+        .features(["./features/integration"])
+        .scenario_regex("^A successful scenario$")
+        .before_all(|context| {
+            Box::pin(async move {
+                context.insert(ServerPort(4000));
+                Ok(())
+            })
+        })
+        .after_all(|context| {
+            Box::pin(async move {
+                let port = context.get::<ServerPort>().unwrap().0;
+                TORN_DOWN_PORT.store(port.into(), Ordering::SeqCst);
+                Ok(())
+            })
+        });
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 1);
+    assert_eq!(results.hooks.total, 2);
+    assert_eq!(results.hooks.passed, 2);
+    assert_eq!(TORN_DOWN_PORT.load(Ordering::SeqCst), 4000);
+}
+
+#[test]
+#[serial]
+fn failing_before_all_hook_skips_the_run() {
+    TORN_DOWN_PORT.store(0, Ordering::SeqCst);
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .before_all(|_context| Box::pin(async { Err("could not start server".into()) }))
+        .after_all(|_context| {
+            Box::pin(async {
+                TORN_DOWN_PORT.store(1, Ordering::SeqCst);
+                Ok(())
+            })
+        });
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.features.total, 0);
+    assert_eq!(results.hooks.failed, 1);
+    assert_eq!(TORN_DOWN_PORT.load(Ordering::SeqCst), 1);
+    assert!(results.failed());
+}
+
+fn hook_panic(event: &CucumberEvent) -> Option<String> {
+    let failure = match event {
+        CucumberEvent::Feature(_, FeatureEvent::Hook(_, HookEvent::Failed(failure))) => failure,
+        CucumberEvent::Feature(
+            _,
+            FeatureEvent::Scenario(_, ScenarioEvent::Hook(_, HookEvent::Failed(failure))),
+        ) => failure,
+        _ => return None,
+    };
+    match failure {
+        HookFailureKind::Panic(_, info) => Some(info.payload.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn panicking_scenario_hooks_fail_the_scenario() {
+    let handler = RecordingHandler::new(hook_panic);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .step_timeout(Duration::from_millis(500))
+        .before(criteria::scenario("A successful scenario"), |_ctx| {
+            Box::pin(async { panic!("database is down") })
+        });
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 4);
+    assert_eq!(results.scenarios.passed, 0);
+    assert_eq!(results.scenarios.failed, 2);
+    assert_eq!(results.hooks.failed, 1);
+    assert_eq!(*handler.records.lock().unwrap(), ["database is down"]);
+}
+
+#[test]
+#[serial]
+fn failing_feature_hooks_fail_every_scenario() {
+    let handler = RecordingHandler::new(hook_panic);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .before(
+            criteria::feature("Variety of scenario outcomes get exposed for integration"),
+            |_ctx| Box::pin(async { panic!("fixtures are missing") }),
+        );
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 4);
+    assert_eq!(results.scenarios.failed, 4);
+    assert_eq!(results.steps.total, 8);
+    assert_eq!(results.steps.skipped, 8);
+    assert_eq!(*handler.records.lock().unwrap(), ["fixtures are missing"]);
+}
+
+static OUTCOMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn describe_outcome(ctx: &LifecycleContext) -> String {
+    let name = match ctx.scenario {
+        Some(ref scenario) => &scenario.name,
+        None => &ctx.feature.name,
+    };
+    match ctx.outcome {
+        Some(Outcome::Failed {
+            ref step,
+            ref panic_info,
+            ..
+        }) => format!(
+            "{}: failed at {:?} with {:?}",
+            name,
+            step.as_ref().map(|s| &s.value),
+            panic_info.as_ref().map(|p| &p.payload),
+        ),
+        ref outcome => format!("{}: {:?}", name, outcome),
+    }
+}
+
+#[test]
+#[serial]
+fn after_hooks_know_the_outcome() {
+    OUTCOMES.lock().unwrap().clear();
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_regex("^A (successful|failing) scenario$")
+        .after(
+            criteria::scenario(regex::Regex::new(".*").unwrap()),
+            |ctx| {
+                OUTCOMES.lock().unwrap().push(describe_outcome(&ctx));
+                Box::pin(async {})
+            },
+        )
+        .after(criteria::feature(regex::Regex::new(".*").unwrap()), |ctx| {
+            OUTCOMES.lock().unwrap().push(describe_outcome(&ctx));
+            Box::pin(async {})
+        });
+
+    futures::executor::block_on(runner.run());
+
+    assert_eq!(
+        *OUTCOMES.lock().unwrap(),
+        [
+            "A successful scenario: Some(Passed)",
+            "A failing scenario: failed at Some(\"it's not okay\") \
+             with Some(\"Intentionally panicking to fail the step\")",
+            "Variety of scenario outcomes get exposed for integration: \
+             failed at Some(\"it's not okay\") \
+             with Some(\"Intentionally panicking to fail the step\")",
+        ]
+    );
+}
+
+#[test]
+#[serial]
+fn after_hooks_run_once_a_scenario_times_out() {
+    OUTCOMES.lock().unwrap().clear();
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_regex("^A timing out scenario$")
+        .scenario_timeout(Duration::from_millis(500))
+        .after(criteria::scenario("A timing out scenario"), |ctx| {
+            let timed_out = matches!(
+                ctx.outcome,
+                Some(Outcome::Failed {
+                    kind: FailureKind::ScenarioTimedOut,
+                    ..
+                })
+            );
+            OUTCOMES
+                .lock()
+                .unwrap()
+                .push(format!("timed out: {}", timed_out));
+            Box::pin(async {})
+        });
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.timed_out, 1);
+    assert_eq!(*OUTCOMES.lock().unwrap(), ["timed out: true"]);
+}
+
+fn scenario_ending(event: &CucumberEvent) -> Option<String> {
+    match event {
+        CucumberEvent::Feature(_, FeatureEvent::Scenario(_, event)) => match event {
+            ScenarioEvent::Retrying(attempt) => Some(format!("retrying {}", attempt)),
+            ScenarioEvent::Skipped => Some("skipped".to_string()),
+            ScenarioEvent::Passed => Some("passed".to_string()),
+            ScenarioEvent::Failed(kind) => Some(format!("failed {:?}", kind)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn failing_after_hooks_fail_skipped_scenarios_once() {
+    let handler = RecordingHandler::new(scenario_ending);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .scenario_regex("^A scenario with an unimplemented step$")
+        .retries(1)
+        .after(
+            criteria::scenario("A scenario with an unimplemented step"),
+            |_ctx| Box::pin(async { panic!("could not clean up") }),
+        );
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 1);
+    assert_eq!(results.scenarios.failed, 1);
+    assert_eq!(results.scenarios.skipped, 0);
+    assert_eq!(*handler.records.lock().unwrap(), ["failed Hook"]);
+}
+
+static SELECTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn record_selection(label: &str, ctx: &LifecycleContext) {
+    let name = match ctx.scenario {
+        Some(ref scenario) => scenario.name.clone(),
+        None => ctx
+            .rule
+            .as_ref()
+            .map(|r| r.name.clone())
+            .unwrap_or_default(),
+    };
+    SELECTED
+        .lock()
+        .unwrap()
+        .push(format!("{}: {}", label, name));
+}
+
+#[test]
+#[serial]
+fn hooks_are_selected_by_inherited_tags() {
+    SELECTED.lock().unwrap().clear();
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/hooks"])
+        .before(
+            criteria::scenario_tag("@db") & !criteria::scenario_tag("@readonly"),
+            |ctx| {
+                record_selection("writes", &ctx);
+                Box::pin(async {})
+            },
+        )
+        .before(criteria::scenario_tag("@audited"), |ctx| {
+            record_selection("audited", &ctx);
+            Box::pin(async {})
+        })
+        .before(criteria::rule_tag("@db"), |ctx| {
+            record_selection("rule", &ctx);
+            Box::pin(async {})
+        })
+        .before(
+            criteria::scenario_tag("@db") & !criteria::rule("An audited rule"),
+            |ctx| {
+                record_selection("outside", &ctx);
+                Box::pin(async {})
+            },
+        );
+
+    futures::executor::block_on(runner.run());
+
+    assert_eq!(
+        *SELECTED.lock().unwrap(),
+        [
+            "writes: A scenario writing to the database",
+            "outside: A scenario writing to the database",
+            "outside: A scenario reading from the database",
+            "rule: An audited rule",
+            "writes: A scenario writing inside the rule",
+            "audited: A scenario writing inside the rule",
+            "audited: A scenario reading inside the rule",
+        ]
+    );
+}
+
+fn rule_background_step(event: &CucumberEvent) -> Option<String> {
+    match event {
+        CucumberEvent::Feature(
+            _,
+            FeatureEvent::Rule(
+                _,
+                RuleEvent::Scenario(_, ScenarioEvent::Background(step, StepEvent::Passed(_))),
+            ),
+        ) => Some(step.value.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn rule_backgrounds_run_after_the_feature_background() {
+    let mut steps = Steps::<StatelessWorld>::new();
+    steps.given("the feature is set up", |world, _step| world);
+    steps.given("the rule is set up", |world, _step| world);
+    steps.then("everything is set up", |world, _step| world);
+
+    let handler = RecordingHandler::new(rule_background_step);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(steps)
+        .features(["./features/background"]);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 1);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        ["the feature is set up", "the rule is set up"]
+    );
+}
+
+fn rule_scenario_progress(event: &CucumberEvent) -> Option<String> {
+    match event {
+        CucumberEvent::Feature(_, FeatureEvent::Rule(_, RuleEvent::Scenario(_, event))) => {
+            match event {
+                ScenarioEvent::Starting(example) => Some(format!("starting {}", example)),
+                ScenarioEvent::Step(step, StepEvent::Passed(_)) => Some(step.value.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn outlines_inside_rules_run_once_per_example() {
+    let mut steps = stateless_steps();
+    steps.when_regex("^the number (\\d+) is picked$", |world, _ctx| world);
+
+    let handler = RecordingHandler::new(rule_scenario_progress);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(steps)
+        .features(["./features/outline"]);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 2);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        [
+            "starting <n> = 1",
+            "the number 1 is picked",
+            "it's okay",
+            "starting <n> = 2",
+            "the number 2 is picked",
+            "it's okay",
+        ]
+    );
+}
+
+fn example_tags(event: &CucumberEvent) -> Option<Vec<String>> {
+    match event {
+        CucumberEvent::Feature(_, FeatureEvent::Scenario(_, ScenarioEvent::Starting(example))) => {
+            Some(example.tags.clone())
+        }
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn examples_carry_their_block_tags() {
+    let handler = RecordingHandler::new(example_tags);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/tags"])
+        .filter_tags("@slow");
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 2);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        [vec![], vec!["slow".to_string()]]
+    );
+}
+
+fn example_block(event: &CucumberEvent) -> Option<(Option<String>, Vec<String>)> {
+    match event {
+        CucumberEvent::Feature(_, FeatureEvent::Scenario(_, ScenarioEvent::Starting(example))) => {
+            Some((example.name.clone(), example.tags.clone()))
+        }
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn every_examples_block_is_expanded() {
+    let handler = RecordingHandler::new(example_block);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/examples"]);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 3);
+    let valid = (Some("valid inputs".to_string()), vec!["fast".to_string()]);
+    let edge = (Some("edge cases".to_string()), vec!["slow".to_string()]);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        [valid.clone(), valid, edge]
+    );
+}
+
+#[test]
+#[serial]
+fn tag_filters_select_examples_blocks() {
+    let handler = RecordingHandler::new(example_block);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/examples"])
+        .filter_tags("@slow");
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 1);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        [(Some("edge cases".to_string()), vec!["slow".to_string()])]
+    );
+}
+
+fn substituted_text(event: &CucumberEvent) -> Option<String> {
+    let (scenario, event) = match event {
+        CucumberEvent::Feature(_, FeatureEvent::Scenario(scenario, event)) => (scenario, event),
+        _ => return None,
+    };
+    match event {
+        ScenarioEvent::Starting(_) => Some(scenario.name.clone()),
+        ScenarioEvent::Step(step, StepEvent::Passed(_)) => Some(match step.table {
+            Some(ref table) => table.rows[1].join(" "),
+            None => step
+                .docstring
+                .clone()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        }),
+        _ => None,
+    }
+}
+
+#[test]
+#[serial]
+fn placeholders_are_substituted_in_step_arguments() {
+    let mut steps = stateless_steps();
+    steps
+        .when("the request is sent", |world, _ctx| world)
+        .then("the response contains", |world, _ctx| world);
+
+    let handler = RecordingHandler::new(substituted_text);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(steps)
+        .features(["./features/placeholders"]);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.passed, 1);
+    assert_eq!(
+        *handler.records.lock().unwrap(),
+        ["Sending alice", "{\"name\": \"alice\"}", "name alice"]
+    );
+}
+
+#[test]
+#[serial]
+fn dry_run_resolves_steps_without_running_them() {
+    TEARDOWNS.store(0, Ordering::SeqCst);
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/integration"])
+        .before_all(|_context| Box::pin(async { Err("must not run".into()) }))
+        .dry_run(true);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(TEARDOWNS.load(Ordering::SeqCst), 0);
+    assert_eq!(results.hooks.total, 0);
+    assert_eq!(results.scenarios.total, 4);
+    assert_eq!(results.scenarios.skipped, 3);
+    assert_eq!(results.scenarios.failed, 1);
+    assert_eq!(results.steps.total, 8);
+    assert_eq!(results.steps.skipped, 8);
+    assert_eq!(results.steps.passed, 0);
+    assert!(results.failed());
+}
+
+fn ambiguous_patterns(event: &CucumberEvent) -> Option<Vec<String>> {
+    match event {
+        CucumberEvent::Feature(
+            _,
+            FeatureEvent::Scenario(_, ScenarioEvent::Step(_, StepEvent::Ambiguous(patterns))),
+        ) => Some(patterns.clone()),
+        _ => None,
+    }
+}
+
+fn ambiguous_steps() -> Steps<StatelessWorld> {
+    let mut steps = stateless_steps();
+    steps
+        .when_regex("^a step (.*)$", |world, _ctx| world)
+        .when_regex("^(.*) two patterns$", |world, _ctx| world);
+    steps
+}
+
+#[test]
+#[serial]
+fn ambiguous_steps_are_reported() {
+    let handler = RecordingHandler::new(ambiguous_patterns);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(ambiguous_steps())
+        .features(["./features/ambiguous"]);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.skipped, 1);
+    assert!(!results.failed());
+    assert_eq!(
+        handler.records.lock().unwrap().concat(),
+        ["^(.*) two patterns$", "^a step (.*)$"]
+    );
+}
+
+#[test]
+#[serial]
+fn ambiguous_steps_can_fail_scenarios() {
+    let runner = Cucumber::with_handler(RecordingHandler::new(ambiguous_patterns))
+        .steps(ambiguous_steps())
+        .features(["./features/ambiguous"])
+        .fail_on_ambiguous_steps(true);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.failed, 1);
+    assert_eq!(results.steps.skipped, 2);
+}
+
+fn parse_step(text: &str) -> cucumber_rust::gherkin::Step {
+    let feature = format!("Feature: A feature\n  Scenario: A scenario\n    {}\n", text);
+    let feature = cucumber_rust::gherkin::Feature::parse(feature, Default::default()).unwrap();
+    feature.scenarios[0].steps[0].clone()
+}
+
+#[test]
+#[serial]
+fn snippets_capture_numbers_and_strings() {
+    let snippet = Snippet::new(&parse_step(
+        r#"Given "alice" has 3 cukes and 1.5 "pickles""#,
+    ));
+
+    assert_eq!(
+        snippet.builder(),
+        r##"steps.given_regex(r#"^"([^"]*)" has (-?\d+) cukes and (-?\d+\.\d+) "([^"]*)"$"#, |world, ctx| {
+    let string: String = ctx.matches[1].parse().unwrap();
+    let int: i64 = ctx.matches[2].parse().unwrap();
+    let float: f64 = ctx.matches[3].parse().unwrap();
+    let string2: String = ctx.matches[4].parse().unwrap();
+    todo!()
+});"##
+    );
+    assert_eq!(
+        snippet.attribute("MyWorld"),
+        r##"#[given(regex = r#"^"([^"]*)" has (-?\d+) cukes and (-?\d+\.\d+) "([^"]*)"$"#)]
+async fn has_cukes_and(world: &mut MyWorld, string: String, int: i64, float: f64, string2: String) {
+    todo!()
+}"##
+    );
+
+    let snippet = Snippet::new(&parse_step("Then it's done (really)"));
+    assert_eq!(
+        snippet.builder(),
+        "steps.then(\"it's done (really)\", |world, ctx| {\n    todo!()\n});"
+    );
+    assert_eq!(
+        snippet.attribute("MyWorld"),
+        "#[then(\"it's done (really)\")]\nasync fn it_s_done_really(world: &mut MyWorld) {\n    todo!()\n}"
+    );
+}
+
+fn scenario_order(order: Order) -> (Vec<String>, Option<u64>) {
+    let handler = RecordingHandler::new(scenario_progress);
+    let runner = Cucumber::with_handler(handler.clone())
+        .steps(stateless_steps())
+        .features(["./features/tags"])
+        .order(order);
+
+    let results = futures::executor::block_on(runner.run());
+    let events = handler.records.lock().unwrap().clone();
+    (events, results.seed)
+}
+
+#[test]
+#[serial]
+fn random_order_is_reproducible_from_its_seed() {
+    let (defined, seed) = scenario_order(Order::Defined);
+    assert_eq!(seed, None);
+
+    let (shuffled, seed) = scenario_order(Order::Random(42));
+    assert_eq!(seed, Some(42));
+    assert_ne!(shuffled, defined);
+    assert_eq!(scenario_order(Order::Random(42)).0, shuffled);
+
+    let mut sorted = shuffled.clone();
+    sorted.sort();
+    let mut expected = defined.clone();
+    expected.sort();
+    assert_eq!(sorted, expected);
+
+    assert_eq!("random:42".parse::<Order>(), Ok(Order::Random(42)));
+    assert_eq!("defined".parse::<Order>(), Ok(Order::Defined));
+    assert!("random:x".parse::<Order>().is_err());
+}
+
+#[test]
+#[serial]
+fn shards_split_the_scenarios_between_them() {
+    let run = |shard: Option<Shard>| {
+        let handler = RecordingHandler::new(scenario_progress);
+        let mut runner = Cucumber::with_handler(handler.clone())
+            .steps(stateless_steps())
+            .features(["./features/tags"]);
+        if let Some(shard) = shard {
+            runner = runner.shard(shard);
+        }
+
+        let results = futures::executor::block_on(runner.run());
+        let events = handler.records.lock().unwrap().clone();
+        (results.scenarios.total, events)
+    };
+
+    let (total, mut expected) = run(None);
+    let mut scenarios = 0;
+    let mut events = vec![];
+    for index in 1..=3 {
+        let shard = format!("{}/3", index).parse::<Shard>().unwrap();
+        let (shard_total, shard_events) = run(Some(shard));
+        assert!(shard_total < total);
+        scenarios += shard_total;
+        events.extend(shard_events);
+    }
+
+    assert_eq!(scenarios, total);
+    events.sort();
+    expected.sort();
+    assert_eq!(events, expected);
+
+    assert!(Shard::new(0, 3).is_none());
+    assert!("4/3".parse::<Shard>().is_err());
+}
+
+#[test]
+#[serial]
+fn failed_scenarios_can_be_rerun() {
+    let rerun_file = std::env::temp_dir().join("cucumber_rust_rerun.txt");
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .features(["./features/rerun"])
+        .rerun_file(&rerun_file);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 4);
+    assert_eq!(results.scenarios.failed, 2);
+    assert_eq!(
+        std::fs::read_to_string(&rerun_file).unwrap(),
+        "features/rerun/rerun.feature:6\nfeatures/rerun/rerun.feature:19\n"
+    );
+
+    let runner = Cucumber::with_handler(CustomEventHandler::default())
+        .steps(stateless_steps())
+        .rerun_from(&rerun_file);
+
+    let results = futures::executor::block_on(runner.run());
+
+    assert_eq!(results.scenarios.total, 2);
+    assert_eq!(results.scenarios.failed, 2);
+}
+
 fn nocapture_enabled() -> bool {
     std::env::args_os().any(|a| {
         if let Some(s) = a.to_str() {
@@ -160,7 +1577,7 @@ fn enable_capture_false_support() {
         return;
     }
     let command_output = Command::new(built_executable_path("capture-runner"))
-        .args(&["false"])
+        .args(["false"])
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .output()
@@ -189,9 +1606,7 @@ fn built_executable_path(name: &str) -> PathBuf {
             .join("debug")
             .join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
 
-    program_path.canonicalize().expect(&format!(
-        "Cannot resolve {} at {:?}",
-        name,
-        program_path.display()
-    ))
+    program_path
+        .canonicalize()
+        .unwrap_or_else(|_| panic!("Cannot resolve {} at {:?}", name, program_path.display()))
 }